@@ -0,0 +1,252 @@
+//! 连接直播间前需要调用的http接口
+//!
+//! 默认使用[`bilibili_client::reqwest_client::Client`]，可以实现[`DanmuInfoApi`]替换成本地的桩、缓存或者自己的网关
+use bilibili_client::{
+    api::live::{danmu_info::RoomInfo, room_play_info::DanmuInfoData},
+    reqwest_client::{Client, ClientError},
+};
+use futures_util::future::BoxFuture;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
+
+use crate::model::{LiveStatus, RoomMetadata};
+
+pub trait DanmuInfoApi {
+    type Error: std::fmt::Debug;
+
+    /// 把房间号（可能是短号）解析为长房间号和主播uid
+    fn get_room_play_info(&self, roomid: u64) -> BoxFuture<'_, Result<RoomInfo, Self::Error>>;
+
+    /// 获取弹幕服务器的token和host列表，`roomid`为长房间号
+    fn get_danmu_info(&self, roomid: u64) -> BoxFuture<'_, Result<DanmuInfoData, Self::Error>>;
+
+    /// 获取直播间和主播的信息，`roomid`为长房间号
+    ///
+    /// 这些信息不影响连接，默认返回`None`
+    fn get_room_metadata(
+        &self,
+        _roomid: u64,
+    ) -> BoxFuture<'_, Result<Option<RoomMetadata>, Self::Error>> {
+        Box::pin(async { Ok(None) })
+    }
+}
+
+impl<A: DanmuInfoApi + ?Sized> DanmuInfoApi for std::sync::Arc<A> {
+    type Error = A::Error;
+
+    fn get_room_play_info(&self, roomid: u64) -> BoxFuture<'_, Result<RoomInfo, Self::Error>> {
+        (**self).get_room_play_info(roomid)
+    }
+
+    fn get_danmu_info(&self, roomid: u64) -> BoxFuture<'_, Result<DanmuInfoData, Self::Error>> {
+        (**self).get_danmu_info(roomid)
+    }
+
+    fn get_room_metadata(
+        &self,
+        roomid: u64,
+    ) -> BoxFuture<'_, Result<Option<RoomMetadata>, Self::Error>> {
+        (**self).get_room_metadata(roomid)
+    }
+}
+
+/// 保存在[`crate::Connector`]中用于刷新token的接口，错误统一转为字符串
+pub(crate) type SharedDanmuInfoApi = std::sync::Arc<dyn DanmuInfoApi<Error = String> + Send + Sync>;
+
+struct ErasedApi<A>(A);
+
+impl<A: DanmuInfoApi + Send + Sync> DanmuInfoApi for ErasedApi<A> {
+    type Error = String;
+
+    fn get_room_play_info(&self, roomid: u64) -> BoxFuture<'_, Result<RoomInfo, String>> {
+        Box::pin(async move {
+            self.0
+                .get_room_play_info(roomid)
+                .await
+                .map_err(|e| format!("{e:?}"))
+        })
+    }
+
+    fn get_danmu_info(&self, roomid: u64) -> BoxFuture<'_, Result<DanmuInfoData, String>> {
+        Box::pin(async move {
+            self.0
+                .get_danmu_info(roomid)
+                .await
+                .map_err(|e| format!("{e:?}"))
+        })
+    }
+
+    fn get_room_metadata(
+        &self,
+        roomid: u64,
+    ) -> BoxFuture<'_, Result<Option<RoomMetadata>, String>> {
+        Box::pin(async move {
+            self.0
+                .get_room_metadata(roomid)
+                .await
+                .map_err(|e| format!("{e:?}"))
+        })
+    }
+}
+
+pub(crate) fn share<A>(api: A) -> SharedDanmuInfoApi
+where
+    A: DanmuInfoApi + Send + Sync + 'static,
+{
+    std::sync::Arc::new(ErasedApi(api))
+}
+
+/// 接口统一的`{code, message, data}`响应
+#[derive(Debug, Deserialize)]
+pub(crate) struct ApiResponse {
+    pub(crate) code: i64,
+    #[serde(default)]
+    pub(crate) message: String,
+    #[serde(default)]
+    pub(crate) data: Value,
+}
+
+/// 各接口共用的错误码，具体的错误类型通过`From`转换
+#[derive(Debug)]
+pub(crate) enum ApiError {
+    /// -101，未登录
+    NotLoggedIn,
+    /// -400，请求参数错误
+    BadRequest {
+        message: String,
+    },
+    Api {
+        code: i64,
+        message: String,
+    },
+}
+
+impl ApiResponse {
+    /// `code`为0时返回`Ok`，否则按共用的错误码转换
+    pub(crate) fn check(code: i64, message: String) -> Result<(), ApiError> {
+        match code {
+            0 => Ok(()),
+            -101 => Err(ApiError::NotLoggedIn),
+            -400 => Err(ApiError::BadRequest { message }),
+            code => Err(ApiError::Api { code, message }),
+        }
+    }
+
+    pub(crate) fn into_data<T, E>(self) -> Result<T, E>
+    where
+        T: DeserializeOwned,
+        E: From<ApiError> + From<serde_json::Error>,
+    {
+        Self::check(self.code, self.message)?;
+        Ok(serde_json::from_value(self.data)?)
+    }
+}
+
+const ROOM_METADATA_URL: &str =
+    "https://api.live.bilibili.com/xlive/web-room/v1/index/getInfoByRoom";
+
+#[derive(Deserialize)]
+struct MetadataResponse {
+    code: i64,
+    data: Option<MetadataData>,
+}
+
+#[derive(Deserialize)]
+struct MetadataData {
+    room_info: MetadataRoomInfo,
+    anchor_info: MetadataAnchorInfo,
+}
+
+#[derive(Deserialize)]
+struct MetadataRoomInfo {
+    room_id: u64,
+    short_id: u64,
+    uid: u64,
+    title: String,
+    area_id: u64,
+    area_name: String,
+    parent_area_id: u64,
+    parent_area_name: String,
+    live_status: u64,
+    live_start_time: u64,
+}
+
+#[derive(Deserialize)]
+struct MetadataAnchorInfo {
+    base_info: MetadataBaseInfo,
+}
+
+#[derive(Deserialize)]
+struct MetadataBaseInfo {
+    uname: String,
+    face: String,
+}
+
+impl From<MetadataData> for RoomMetadata {
+    fn from(data: MetadataData) -> Self {
+        let room = data.room_info;
+        let live_status = LiveStatus::from(room.live_status);
+        RoomMetadata {
+            room_id: room.room_id,
+            short_id: room.short_id,
+            uid: room.uid,
+            uname: data.anchor_info.base_info.uname,
+            face: data.anchor_info.base_info.face,
+            title: room.title,
+            area_id: room.area_id,
+            area_name: room.area_name,
+            parent_area_id: room.parent_area_id,
+            parent_area_name: room.parent_area_name,
+            live_status,
+            live_start_time: (live_status == LiveStatus::Live && room.live_start_time != 0)
+                .then_some(room.live_start_time),
+        }
+    }
+}
+
+async fn fetch_room_metadata(client: &Client, roomid: u64) -> Option<RoomMetadata> {
+    let resp = client
+        .inner()
+        .get(ROOM_METADATA_URL)
+        .query(&[("room_id", roomid)])
+        .send()
+        .await;
+    let resp = match resp {
+        Ok(resp) => resp.json::<MetadataResponse>().await,
+        Err(e) => Err(e),
+    };
+    match resp {
+        Ok(MetadataResponse {
+            code: 0,
+            data: Some(data),
+        }) => Some(data.into()),
+        Ok(MetadataResponse { code, .. }) => {
+            log::warn!("get room metadata of {} failed, code {}", roomid, code);
+            None
+        }
+        Err(e) => {
+            log::warn!("get room metadata of {} error: {}", roomid, e);
+            None
+        }
+    }
+}
+
+impl DanmuInfoApi for Client {
+    type Error = ClientError;
+
+    fn get_room_play_info(&self, roomid: u64) -> BoxFuture<'_, Result<RoomInfo, Self::Error>> {
+        Box::pin(Client::get_room_play_info(self, roomid))
+    }
+
+    fn get_danmu_info(&self, roomid: u64) -> BoxFuture<'_, Result<DanmuInfoData, Self::Error>> {
+        Box::pin(Client::get_danmu_info(self, roomid))
+    }
+
+    /// 请求失败时只记录日志，不影响初始化
+    fn get_room_metadata(
+        &self,
+        roomid: u64,
+    ) -> BoxFuture<'_, Result<Option<RoomMetadata>, Self::Error>> {
+        Box::pin(async move { Ok(fetch_room_metadata(self, roomid).await) })
+    }
+}
@@ -12,10 +12,91 @@ pub struct BlindGiftInfo {
     original_gift_id: u64,
     original_gift_name: String,
 }
+#[derive(Debug, serde::Deserialize)]
+pub struct CommonNoticeSegment {
+    text: String,
+}
+#[derive(Debug, serde::Deserialize)]
+pub struct RedPocketAwardInfo {
+    award_name: String,
+    award_price: Money,
+}
+#[derive(Debug, serde::Deserialize)]
+pub struct PkSettleWinner {
+    room_id: u64,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum U64OrStr {
+    U64(u64),
+    Str(String),
+}
+
+/// 缺少`msg_type`的`INTERACT_WORD`是进入直播间
+fn default_msg_type() -> u64 {
+    1
+}
+
+fn de_u64_or_str<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match U64OrStr::deserialize(deserializer)? {
+        U64OrStr::U64(n) => Ok(n),
+        U64OrStr::Str(s) => s.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+fn de_str_or_u64<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match U64OrStr::deserialize(deserializer)? {
+        U64OrStr::U64(n) => Ok(n.to_string()),
+        U64OrStr::Str(s) => Ok(s),
+    }
+}
 
 #[derive(Debug, serde::Deserialize)]
 #[serde(tag = "cmd", content = "data", rename_all = "SCREAMING_SNAKE_CASE")]
 pub(crate) enum Cmd {
+    AnchorLotAward {
+        id: u64,
+        award_name: String,
+        award_num: u64,
+        #[serde(default)]
+        award_image: String,
+        award_users: Vec<User>,
+    },
+    AnchorLotCheckstatus {
+        id: u64,
+        status: u64,
+        #[serde(default)]
+        reject_reason: String,
+    },
+    AnchorLotStart {
+        id: u64,
+        award_name: String,
+        award_num: u64,
+        #[serde(default)]
+        award_image: String,
+        #[serde(default)]
+        danmu: String,
+        #[serde(default)]
+        require_text: String,
+        #[serde(default)]
+        gift_id: u64,
+        #[serde(default)]
+        gift_name: String,
+        #[serde(default)]
+        gift_num: u64,
+        #[serde(default)]
+        gift_price: Money,
+        max_time: u64,
+        time: u64,
+        current_time: u64,
+    },
     ComboSend {
         action: String,
         batch_combo_num: u64,
@@ -24,12 +105,20 @@ pub(crate) enum Cmd {
         gift_id: u64,
         user: User,
     },
-    CommonNoticeDanmaku {},
-    EntryEffect {},
+    CommonNoticeDanmaku {
+        content_segments: Vec<CommonNoticeSegment>,
+    },
+    EntryEffect {
+        uid: u64,
+        face: String,
+        copy_writing: String,
+        #[serde(default)]
+        privilege_type: GuardLevel,
+    },
     GuardBuy {
         gift_id: u64,
         gift_name: String,
-        guard_level: u64,
+        guard_level: GuardLevel,
         price: u64,
         num: u64,
         uid: u64,
@@ -47,13 +136,99 @@ pub(crate) enum Cmd {
         uname: String,
         face: String,
     },
+    LikeInfoV3Click {
+        #[serde(flatten)]
+        user: User,
+        fans_medal: Option<FansMedal>,
+        like_text: String,
+    },
+    LikeInfoV3Update {
+        click_count: u64,
+    },
     LiveInteractiveGame {},
+    NoticeMsg {
+        msg_common: String,
+        #[serde(default)]
+        real_roomid: u64,
+        #[serde(default)]
+        link_url: String,
+    },
     OnlineRankV2 {},
     OnlineRankTop3 {
         dmscore: u64,
         list: Vec<OnlineRankTop3ListItem>,
     },
-    PopularityRedPocketStart {},
+    PkBattleStartNew {
+        #[serde(deserialize_with = "de_u64_or_str")]
+        pk_id: u64,
+        pk_start_time: u64,
+        pk_frozen_time: u64,
+        pk_end_time: u64,
+        init_info: PkSide,
+        match_info: PkSide,
+    },
+    PkBattleProcessNew {
+        #[serde(deserialize_with = "de_u64_or_str")]
+        pk_id: u64,
+        init_info: PkSide,
+        match_info: PkSide,
+    },
+    PkBattleEnd {
+        #[serde(deserialize_with = "de_u64_or_str")]
+        pk_id: u64,
+        init_info: PkSide,
+        match_info: PkSide,
+    },
+    PkBattleSettleV2 {
+        #[serde(deserialize_with = "de_u64_or_str")]
+        pk_id: u64,
+        result_type: i64,
+        winner: Option<PkSettleWinner>,
+    },
+    PopularityRedPocketStart {
+        lot_id: u64,
+        sender_uid: u64,
+        sender_name: String,
+        sender_face: String,
+        danmu: String,
+        awards: Vec<RedPocketAward>,
+        total_price: Money,
+        start_time: u64,
+        end_time: u64,
+    },
+    PopularityRedPocketWinnerList {
+        lot_id: u64,
+        total_num: u64,
+        /// 每一项为 `[uid, uname, 中奖记录id, award_id, ...]`
+        winner_info: Vec<Vec<Value>>,
+        awards: HashMap<String, RedPocketAwardInfo>,
+    },
+    RecallDanmuMsg {
+        #[serde(deserialize_with = "de_str_or_u64")]
+        target_id: String,
+    },
+    RoomBlockMsg {
+        uid: u64,
+        uname: String,
+        operator: u64,
+    },
+    RoomSilentOn {
+        #[serde(rename = "type")]
+        kind: SilenceKind,
+        level: u64,
+        second: i64,
+    },
+    RoomSilentOff {},
+    #[cfg(feature = "open_platform")]
+    LiveOpenPlatformDm(crate::open_platform::OpenDanmaku),
+    #[cfg(feature = "open_platform")]
+    LiveOpenPlatformSendGift(crate::open_platform::OpenGift),
+    #[cfg(feature = "open_platform")]
+    LiveOpenPlatformSuperChat(crate::open_platform::OpenSuperChat),
+    #[cfg(feature = "open_platform")]
+    LiveOpenPlatformGuard(crate::open_platform::OpenGuard),
+    #[cfg(feature = "open_platform")]
+    LiveOpenPlatformLike(crate::open_platform::OpenLike),
     RoomRealTimeMessageUpdate {
         fans: u64,
         fans_club: u64,
@@ -64,10 +239,27 @@ pub(crate) enum Cmd {
     StopLiveRoomList {
         room_id_list: Vec<u64>,
     },
+    Live {
+        #[serde(deserialize_with = "de_u64_or_str")]
+        roomid: u64,
+        #[serde(default)]
+        live_time: u64,
+    },
+    Preparing {
+        #[serde(deserialize_with = "de_u64_or_str")]
+        roomid: u64,
+    },
     InteractWord {
+        /// 1 进入，2 关注，3 分享，4 特别关注，5 互相关注，旧版协议中没有这个字段
+        #[serde(default = "default_msg_type")]
+        msg_type: u64,
         fans_medal: Option<FansMedal>,
         #[serde(flatten)]
         user: User,
+        #[serde(default)]
+        timestamp: u64,
+        #[serde(default)]
+        score: u64,
     },
     WatchedChange {
         num: u64,
@@ -75,7 +267,15 @@ pub(crate) enum Cmd {
     OnlineRankCount {
         count: u64,
     },
+    DanmuAggregation {
+        activity_identity: String,
+        activity_source: u64,
+        aggregation_num: u64,
+        msg: String,
+        timestamp: u64,
+    },
     DanmuMsg {
+        id: Option<String>,
         danmaku_type: u64,
         fans_medal: Option<FansMedal>,
         user: User,
@@ -125,8 +325,9 @@ pub(crate) enum Cmd {
     },
 }
 
-use std::fmt::Display;
+use std::{collections::HashMap, fmt::Display};
 
+use serde::Deserialize;
 use serde_json::Value;
 
 use crate::{event::EventData, model::*};
@@ -180,9 +381,40 @@ impl Cmd {
             Value::String(cmd) => {
                 const PROTOCOL_ERROR: &str = "danmu_msg事件协议错误";
                 match cmd.as_str() {
-                    "NOTICE_MSG" | "WIDGET_BANNER" | "HOT_RANK_CHANGED" | "HOT_RANK_SETTLEMENT" => {
+                    // WIDGET_BANNER是直播间页面挂件的配置，挂件的数据是给页面渲染用的url编码的json，
+                    // 不是通知文本，所以不作为NoticeEvent
+                    "WIDGET_BANNER" | "HOT_RANK_CHANGED" | "HOT_RANK_SETTLEMENT" => {
                         Err(CmdDeserError::Ignored { tag: cmd.clone() })
                     }
+                    "NOTICE_MSG" | "LIVE" | "PREPARING" => {
+                        // 这些指令没有data字段，内容和cmd同级
+                        let val = serde_json::json!({ "cmd": cmd, "data": val });
+                        serde_json::from_value(val.clone()).map_err(|json_error| {
+                            CmdDeserError::CannotDeser {
+                                json_error,
+                                text: val.to_string(),
+                            }
+                        })
+                    }
+                    "PK_BATTLE_START_NEW"
+                    | "PK_BATTLE_PROCESS_NEW"
+                    | "PK_BATTLE_END"
+                    | "PK_BATTLE_SETTLE_V2" => {
+                        // pk_id 等字段和data同级，需要先挪到data里
+                        let mut val = val.clone();
+                        for key in ["pk_id", "pk_status", "timestamp"] {
+                            let field = val[key].take();
+                            if let Some(data) = val["data"].as_object_mut() {
+                                data.entry(key).or_insert(field);
+                            }
+                        }
+                        serde_json::from_value(val.clone()).map_err(|json_error| {
+                            CmdDeserError::CannotDeser {
+                                json_error,
+                                text: val.to_string(),
+                            }
+                        })
+                    }
                     "DANMU_MSG" => {
                         // 如果这里出问题，可能是b站协议发生变更了，所以panic一下无可厚非吧
                         let info = val["info"].as_array().expect(PROTOCOL_ERROR);
@@ -190,8 +422,10 @@ impl Cmd {
                         let user = info[2].as_array().expect(PROTOCOL_ERROR);
                         let uid = user[0].as_u64().expect(PROTOCOL_ERROR);
                         let name = user[1].as_str().expect(PROTOCOL_ERROR);
-                        let ts = info[10].as_object().expect(PROTOCOL_ERROR)["ts"]
+                        // ts 所在的位置在不同版本的协议中不一样
+                        let ts = info[9]["ts"]
                             .as_u64()
+                            .or_else(|| info[10]["ts"].as_u64())
                             .expect(PROTOCOL_ERROR);
                         let danmaku_type = info[0].as_array().expect(PROTOCOL_ERROR)[10]
                             .as_u64()
@@ -201,7 +435,7 @@ impl Cmd {
                             let medal_level = fans_medal[0].as_u64();
                             let medal_name = fans_medal[1].as_str();
                             let anchor_roomid = fans_medal[3].as_u64();
-                            let guard_level = fans_medal[10].as_u64();
+                            let guard_level = fans_medal[10].as_u64().map(GuardLevel::from);
                             if let (
                                 Some(medal_level),
                                 Some(medal_name),
@@ -239,15 +473,23 @@ impl Cmd {
                         } else {
                             None
                         };
+                        // 弹幕id，用于和撤回消息对应
+                        let id = info[0][15]["extra"]
+                            .as_str()
+                            .and_then(|extra| serde_json::from_str::<Value>(extra).ok())
+                            .and_then(|extra| extra["id_str"].as_str().map(ToOwned::to_owned));
                         // 是否为抽奖弹幕？
 
                         let res = Cmd::DanmuMsg {
+                            id,
                             danmaku_type,
                             fans_medal,
                             user: User {
                                 uname: name.to_owned(),
                                 uid,
                                 face: None,
+                                level: None,
+                                open_id: None,
                             },
                             message: message.to_owned(),
                             emoticon,
@@ -272,13 +514,60 @@ impl Cmd {
     pub fn into_event(self) -> Option<EventData> {
         use crate::event::*;
         match self {
-            Cmd::InteractWord { fans_medal, user } => {
-                Some(EventData::EnterRoomEvent(EnterRoomEvent {
-                    user,
-                    fans_medal: medal_filter(fans_medal),
-                }))
+            Cmd::InteractWord {
+                msg_type,
+                fans_medal,
+                user,
+                timestamp,
+                score,
+            } => {
+                let fans_medal = medal_filter(fans_medal);
+                match msg_type {
+                    3 => Some(
+                        ShareEvent {
+                            user,
+                            fans_medal,
+                            timestamp,
+                            score,
+                        }
+                        .into(),
+                    ),
+                    2 | 4 | 5 => {
+                        let kind = match msg_type {
+                            2 => FollowKind::Follow,
+                            4 => FollowKind::SpecialFollow,
+                            _ => FollowKind::MutualFollow,
+                        };
+                        Some(
+                            FollowEvent {
+                                kind,
+                                user,
+                                fans_medal,
+                                timestamp,
+                                score,
+                            }
+                            .into(),
+                        )
+                    }
+                    // 未知的类型和旧版协议一样作为进入直播间
+                    msg_type => {
+                        if msg_type != 1 {
+                            log::debug!("unknown interact msg_type: {}", msg_type);
+                        }
+                        Some(
+                            EnterRoomEvent {
+                                user,
+                                fans_medal,
+                                timestamp,
+                                score,
+                            }
+                            .into(),
+                        )
+                    }
+                }
             }
             Cmd::DanmuMsg {
+                id,
                 danmaku_type,
                 fans_medal,
                 user,
@@ -287,7 +576,8 @@ impl Cmd {
                 ts,
             } => match emoticon {
                 Some(emoticon) => Some(EventData::DanmakuEvent(DanmakuEvent {
-                    flag: danmaku_type,
+                    id,
+                    flag: DanmakuFlags(danmaku_type),
                     message: DanmakuMessage::Emoticon {
                         alt_message: message,
                         emoticon,
@@ -297,13 +587,71 @@ impl Cmd {
                     ts,
                 })),
                 None => Some(EventData::DanmakuEvent(DanmakuEvent {
-                    flag: danmaku_type,
+                    id,
+                    flag: DanmakuFlags(danmaku_type),
                     message: DanmakuMessage::Plain { message },
                     user,
                     fans_medal,
                     ts,
                 })),
             },
+            Cmd::RecallDanmuMsg { target_id } => Some(DanmakuRecallEvent { id: target_id }.into()),
+            Cmd::RoomBlockMsg {
+                uid,
+                uname,
+                operator,
+            } => Some(
+                UserBlockedEvent {
+                    user: User {
+                        uid,
+                        uname,
+                        face: None,
+                        level: None,
+                        open_id: None,
+                    },
+                    operator,
+                }
+                .into(),
+            ),
+            Cmd::RoomSilentOn {
+                kind,
+                level,
+                second,
+            } => Some(
+                RoomSilentOnEvent {
+                    kind,
+                    level,
+                    second,
+                }
+                .into(),
+            ),
+            Cmd::RoomSilentOff {} => Some(RoomSilentOffEvent {}.into()),
+            #[cfg(feature = "open_platform")]
+            Cmd::LiveOpenPlatformDm(dm) => Some(dm.into_event()),
+            #[cfg(feature = "open_platform")]
+            Cmd::LiveOpenPlatformSendGift(gift) => Some(gift.into_event()),
+            #[cfg(feature = "open_platform")]
+            Cmd::LiveOpenPlatformSuperChat(sc) => Some(sc.into_event()),
+            #[cfg(feature = "open_platform")]
+            Cmd::LiveOpenPlatformGuard(guard) => Some(guard.into_event()),
+            #[cfg(feature = "open_platform")]
+            Cmd::LiveOpenPlatformLike(like) => Some(like.into_event()),
+            Cmd::DanmuAggregation {
+                activity_identity,
+                activity_source,
+                aggregation_num,
+                msg,
+                timestamp,
+            } => Some(
+                DanmakuAggregationEvent {
+                    activity_identity,
+                    activity_source,
+                    num: aggregation_num,
+                    message: msg,
+                    timestamp,
+                }
+                .into(),
+            ),
             Cmd::SuperChatMessage {
                 uid,
                 medal_info,
@@ -315,9 +663,11 @@ impl Cmd {
                     uid,
                     uname: user_info.uname,
                     face: Some(user_info.face),
+                    level: None,
+                    open_id: None,
                 },
                 fans_medal: medal_info,
-                price,
+                price: Money::from_cny(price),
                 message,
                 message_jpn: None,
             })),
@@ -333,9 +683,11 @@ impl Cmd {
                     uid,
                     uname: user_info.uname,
                     face: Some(user_info.face),
+                    level: None,
+                    open_id: None,
                 },
                 fans_medal: medal_info,
-                price,
+                price: Money::from_cny(price),
                 message,
                 message_jpn: Some(message_jpn),
             })),
@@ -369,9 +721,8 @@ impl Cmd {
                             num,
                             gift_name,
                             gift_id,
-                            price,
-                            coin_type,
-                            coin_count: total_coin,
+                            price: Coin::new(coin_type, price),
+                            total: Coin::new(coin_type, total_coin),
                         },
                         rnd,
                     }))
@@ -385,9 +736,8 @@ impl Cmd {
                             num,
                             gift_name,
                             gift_id,
-                            price,
-                            coin_type,
-                            coin_count: total_coin,
+                            price: Coin::new(coin_type, price),
+                            total: Coin::new(coin_type, total_coin),
                         },
                         rnd,
                     }))
@@ -430,16 +780,26 @@ impl Cmd {
             } => Some(
                 GuardBuyEvent {
                     level: guard_level,
-                    price,
+                    price: Money::from_gold(price),
                     user: User {
                         uname: username,
                         uid,
                         face: None,
+                        level: None,
+                        open_id: None,
                     },
                 }
                 .into(),
             ),
             Cmd::StopLiveRoomList { room_id_list } => Some(StopLiveEvent { room_id_list }.into()),
+            Cmd::Live { roomid, live_time } => Some(
+                LiveStartEvent {
+                    room_id: roomid,
+                    live_time,
+                }
+                .into(),
+            ),
+            Cmd::Preparing { roomid } => Some(LiveStopEvent { room_id: roomid }.into()),
             Cmd::OnlineRankCount { count } => Some(OnlineRankCountEvent { count }.into()),
             Cmd::RoomChange {
                 area_id,
@@ -461,6 +821,255 @@ impl Cmd {
                 }
                 .into(),
             ),
+            Cmd::AnchorLotStart {
+                id,
+                award_name,
+                award_num,
+                award_image,
+                danmu,
+                require_text,
+                gift_id,
+                gift_name,
+                gift_num,
+                gift_price,
+                max_time,
+                time,
+                current_time,
+            } => Some(
+                AnchorLotteryStartEvent {
+                    id,
+                    award_name,
+                    award_num,
+                    award_image,
+                    danmu,
+                    require_text,
+                    gift: (gift_id != 0).then_some(LotteryGiftRequirement {
+                        gift_id,
+                        gift_name,
+                        gift_num,
+                        gift_price,
+                    }),
+                    max_time,
+                    time,
+                    current_time,
+                }
+                .into(),
+            ),
+            Cmd::AnchorLotCheckstatus {
+                id,
+                status,
+                reject_reason,
+            } => Some(
+                AnchorLotteryCheckEvent {
+                    id,
+                    status,
+                    reject_reason,
+                }
+                .into(),
+            ),
+            Cmd::AnchorLotAward {
+                id,
+                award_name,
+                award_num,
+                award_image,
+                award_users,
+            } => Some(
+                AnchorLotteryAwardEvent {
+                    id,
+                    award_name,
+                    award_num,
+                    award_image,
+                    winners: award_users,
+                }
+                .into(),
+            ),
+            Cmd::PopularityRedPocketStart {
+                lot_id,
+                sender_uid,
+                sender_name,
+                sender_face,
+                danmu,
+                awards,
+                total_price,
+                start_time,
+                end_time,
+            } => Some(
+                RedPocketStartEvent {
+                    lot_id,
+                    sender: User {
+                        uid: sender_uid,
+                        uname: sender_name,
+                        face: Some(sender_face),
+                        level: None,
+                        open_id: None,
+                    },
+                    danmu,
+                    awards,
+                    total_price,
+                    start_time,
+                    end_time,
+                }
+                .into(),
+            ),
+            Cmd::PopularityRedPocketWinnerList {
+                lot_id,
+                total_num,
+                winner_info,
+                awards,
+            } => {
+                let winners = winner_info
+                    .iter()
+                    .filter_map(|info| {
+                        let user = match (info.first().and_then(Value::as_u64), info.get(1)) {
+                            (Some(uid), Some(Value::String(uname))) => User {
+                                uid,
+                                uname: uname.clone(),
+                                face: None,
+                                level: None,
+                                open_id: None,
+                            },
+                            _ => {
+                                log::warn!("unexpected red pocket winner: {:?}", info);
+                                return None;
+                            }
+                        };
+                        let award_id = info.get(3).and_then(Value::as_u64);
+                        let award = award_id.and_then(|award_id| {
+                            let award = awards.get(&award_id.to_string())?;
+                            Some(RedPocketWinnerAward {
+                                award_id,
+                                award_name: award.award_name.clone(),
+                                award_price: award.award_price,
+                            })
+                        });
+                        if award.is_none() {
+                            log::debug!("unknown red pocket award: {:?}", info);
+                        }
+                        Some(RedPocketWinner { user, award })
+                    })
+                    .collect();
+                Some(
+                    RedPocketWinnerEvent {
+                        lot_id,
+                        total_num,
+                        winners,
+                    }
+                    .into(),
+                )
+            }
+            Cmd::EntryEffect {
+                uid,
+                face,
+                copy_writing,
+                privilege_type,
+            } => Some(
+                EntryEffectEvent {
+                    uid,
+                    face,
+                    copy_writing,
+                    privilege_type,
+                }
+                .into(),
+            ),
+            Cmd::NoticeMsg {
+                msg_common,
+                real_roomid,
+                link_url,
+            } => Some(
+                NoticeEvent {
+                    kind: NoticeKind::Broadcast,
+                    message: msg_common,
+                    room_id: (real_roomid != 0).then_some(real_roomid),
+                    link_url: (!link_url.is_empty()).then_some(link_url),
+                }
+                .into(),
+            ),
+            Cmd::CommonNoticeDanmaku { content_segments } => Some(
+                NoticeEvent {
+                    kind: NoticeKind::Common,
+                    message: content_segments.into_iter().map(|seg| seg.text).collect(),
+                    room_id: None,
+                    link_url: None,
+                }
+                .into(),
+            ),
+            Cmd::LikeInfoV3Click {
+                user,
+                fans_medal,
+                like_text,
+            } => Some(
+                LikeEvent {
+                    user,
+                    fans_medal: medal_filter(fans_medal),
+                    like_text,
+                }
+                .into(),
+            ),
+            Cmd::LikeInfoV3Update { click_count } => {
+                Some(LikeCountUpdateEvent { click_count }.into())
+            }
+            Cmd::PkBattleStartNew {
+                pk_id,
+                pk_start_time,
+                pk_frozen_time,
+                pk_end_time,
+                init_info,
+                match_info,
+            } => Some(
+                PkStartEvent {
+                    pk_id,
+                    init_info,
+                    match_info,
+                    start_time: pk_start_time,
+                    frozen_time: pk_frozen_time,
+                    end_time: pk_end_time,
+                }
+                .into(),
+            ),
+            Cmd::PkBattleProcessNew {
+                pk_id,
+                init_info,
+                match_info,
+            } => Some(
+                PkProgressEvent {
+                    pk_id,
+                    init_info,
+                    match_info,
+                }
+                .into(),
+            ),
+            Cmd::PkBattleEnd {
+                pk_id,
+                init_info,
+                match_info,
+            } => {
+                let winner = match init_info.votes.cmp(&match_info.votes) {
+                    std::cmp::Ordering::Greater => Some(init_info.room_id),
+                    std::cmp::Ordering::Less => Some(match_info.room_id),
+                    std::cmp::Ordering::Equal => None,
+                };
+                Some(
+                    PkEndEvent {
+                        pk_id,
+                        init_info,
+                        match_info,
+                        winner,
+                    }
+                    .into(),
+                )
+            }
+            Cmd::PkBattleSettleV2 {
+                pk_id,
+                result_type,
+                winner,
+            } => Some(
+                PkSettleEvent {
+                    pk_id,
+                    result_type,
+                    winner: winner.map(|w| w.room_id).filter(|id| *id != 0),
+                }
+                .into(),
+            ),
             rest => {
                 log::debug!("unhandled cmd: {:?}", rest);
                 None
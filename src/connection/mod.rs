@@ -8,6 +8,7 @@ pub enum WsConnectError {
     JsError(gloo_utils::errors::JsError),
     UnexpecedEnd,
     AuthFailed,
+    Timeout,
 }
 
 impl std::fmt::Display for WsConnectError {
@@ -22,12 +23,111 @@ impl std::fmt::Display for WsConnectError {
             WsError(e) => write!(f, "WebSocket错误：{}", e),
             UnexpecedEnd => write!(f, "连接意外关闭"),
             AuthFailed => write!(f, "鉴权失败"),
+            Timeout => write!(f, "连接超时"),
         }
     }
 }
 
 impl std::error::Error for WsConnectError {}
 
+/// 连接的生命周期状态
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "state", content = "data")]
+pub enum ConnectionState {
+    /// 开始连接某个host
+    Connecting,
+    /// 鉴权成功，开始接收事件
+    Authenticated,
+    /// 收到心跳回复
+    HeartbeatOk,
+    /// 连接失败，切换到下一个host
+    HostSwitched { from: String },
+    /// 鉴权失败，刷新token后重新连接
+    Reconnecting,
+    /// 在原连接上切换了直播间
+    RoomChanged { from: u64 },
+    /// 服务器发送了关闭帧
+    ClosedByServer,
+    /// 因为错误断开
+    Disconnected { reason: String },
+    /// 连接流结束
+    Closed,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ConnectionStateEvent {
+    pub state: ConnectionState,
+    pub room_id: u64,
+    pub host: String,
+    pub time: chrono::DateTime<chrono::Utc>,
+}
+
+pub type LifecycleReceiver = futures::channel::mpsc::Receiver<ConnectionStateEvent>;
+pub(crate) type LifecycleSender = futures::channel::mpsc::Sender<ConnectionStateEvent>;
+
+/// 状态通道的容量，接收端处理不及时时丢弃新的状态
+pub(crate) const LIFECYCLE_BUFFER_SIZE: usize = 64;
+
+/// 向[`crate::Connector::lifecycle`]返回的接收端发送状态，没有订阅时什么也不做
+#[derive(Debug, Clone)]
+pub(crate) struct LifecycleEmitter {
+    pub(crate) tx: Option<LifecycleSender>,
+    pub(crate) room_id: u64,
+    pub(crate) host: String,
+}
+
+impl LifecycleEmitter {
+    pub(crate) fn emit(&mut self, state: ConnectionState) {
+        if let Some(tx) = &mut self.tx {
+            let event = ConnectionStateEvent {
+                state,
+                room_id: self.room_id,
+                host: self.host.clone(),
+                time: chrono::Utc::now(),
+            };
+            match tx.try_send(event) {
+                Ok(()) => {}
+                Err(e) if e.is_full() => {
+                    log::debug!("lifecycle channel is full, drop {:?}", e.into_inner().state)
+                }
+                // 接收端被丢弃时忽略
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+/// 解析数据包中的事件，各个运行时的连接共用
+///
+/// `receive_notice`为`false`时丢弃[`crate::event::NoticeEvent`]
+pub(crate) fn parse_packet(
+    packet: crate::packet::RawPacket<'_>,
+    receive_notice: bool,
+) -> Vec<crate::event::EventData> {
+    use crate::event::EventData;
+    packet
+        .get_datas()
+        .into_iter()
+        .filter_map(|data| match data.into_event_data() {
+            Ok(Some(EventData::NoticeEvent(_))) if !receive_notice => None,
+            Ok(data) => data,
+            Err(e) => {
+                log::warn!("解析数据包失败：{}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// 放入事件缓冲区，超过`limit`时丢弃最早的事件
+pub(crate) fn push_bounded<T>(buffer: &mut std::collections::VecDeque<T>, item: T, limit: usize) {
+    if buffer.len() >= limit.max(1) {
+        buffer.pop_front();
+        log::warn!("event buffer is full, drop the oldest event");
+    }
+    buffer.push_back(item);
+}
+
 #[derive(Debug, Clone)]
 pub enum EventStreamError {
     ConnectionClosed,
@@ -49,7 +149,7 @@ impl std::error::Error for EventStreamError {}
 #[cfg(feature = "rt_tokio")]
 mod tokio_connection;
 #[cfg(feature = "rt_tokio")]
-pub use tokio_connection::TokioConnection as Connection;
+pub use tokio_connection::{PacketSender, TokioConnection as Connection};
 
 // #[cfg(feature = "rt_tokio")]
 // pub mod multi_stream;
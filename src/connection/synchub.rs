@@ -1,18 +1,22 @@
 use futures_util::Stream;
 use std::{
-    collections::{hash_map::DefaultHasher, HashMap, VecDeque, HashSet},
+    collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
     hash::{Hash, Hasher},
+    ops::AddAssign,
     pin::Pin,
     sync::atomic::{AtomicU64, Ordering},
-    task::Poll, ops::AddAssign,
+    task::Poll,
 };
 
+use super::{ConnectionState, LifecycleEmitter};
 use crate::event::Event;
 type SyncChannelId = u64;
 #[derive(Debug, Default)]
 pub struct SyncHub {
     next_id: AtomicU64,
     pub channels: HashMap<SyncChannelId, SyncChannel>,
+    /// 有通道结束时发送[`ConnectionState::Disconnected`]，全部结束时发送[`ConnectionState::Closed`]
+    pub(crate) lifecycle: Option<LifecycleEmitter>,
 }
 
 impl SyncHub {
@@ -71,32 +75,63 @@ impl SyncHub {
 impl Stream for SyncHub {
     type Item = Event;
 
+    /// 所有通道都结束后结束
     fn poll_next(
         mut self: Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        let mut new_event = None;
-        for (id, chan) in self.channels.iter_mut() {
-            if let Poll::Ready(Some(event)) = chan.backend.as_mut().poll_next(cx) {
-                if env!("CARGO_PKG_VERSION") != event.meta.lib_version {
-                    log::warn!(
-                        "版本不匹配：本地版本 {}，数据源版本 {}, 数据源： {:?}",
-                        env!("CARGO_PKG_VERSION"),
-                        event.meta.lib_version,
-                        event.meta.source
-                    );
-                } else {
-                    new_event = Some((*id, event));
-                    break;
+        loop {
+            let mut new_event = None;
+            let mut progressed = false;
+            let mut ended = vec![];
+            for (id, chan) in self.channels.iter_mut() {
+                match chan.backend.as_mut().poll_next(cx) {
+                    Poll::Ready(Some(event)) => {
+                        if env!("CARGO_PKG_VERSION") != event.meta.lib_version {
+                            log::warn!(
+                                "版本不匹配：本地版本 {}，数据源版本 {}, 数据源： {:?}",
+                                env!("CARGO_PKG_VERSION"),
+                                event.meta.lib_version,
+                                event.meta.source
+                            );
+                            progressed = true;
+                        } else {
+                            new_event = Some((*id, event));
+                            break;
+                        }
+                    }
+                    Poll::Ready(None) => ended.push(*id),
+                    Poll::Pending => {}
                 }
             }
-        }
-        if let Some((id, event)) = new_event {
-            if let Some(event) = self.push(id, event) {
-                return Poll::Ready(Some(event));
+            for id in ended {
+                self.channels.remove(&id);
+                let remaining = self.channels.len();
+                if remaining > 0 {
+                    if let Some(lifecycle) = self.lifecycle.as_mut() {
+                        lifecycle.emit(ConnectionState::Disconnected {
+                            reason: format!("sync channel {} ended, {} remaining", id, remaining),
+                        });
+                    }
+                }
+            }
+            if let Some((id, event)) = new_event {
+                if let Some(event) = self.push(id, event) {
+                    return Poll::Ready(Some(event));
+                }
+                // 重复的事件，继续读取
+                continue;
+            }
+            if self.channels.is_empty() {
+                if let Some(mut lifecycle) = self.lifecycle.take() {
+                    lifecycle.emit(ConnectionState::Closed);
+                }
+                return Poll::Ready(None);
+            }
+            if !progressed {
+                return Poll::Pending;
             }
         }
-        Poll::Pending
     }
 }
 
@@ -5,9 +5,10 @@ use std::collections::VecDeque;
 // use tungstenite;
 use crate::{
     connection::WsConnectError,
-    event::{Event, EventMeta, EventSource},
-    packet::{Auth, Operation, RawPacket},
-    Connector,
+    connector::SharedMetadata,
+    event::{Event, EventData, EventMeta, EventSource},
+    packet::{Operation, RawPacket},
+    ConnectError, Connector,
 };
 use tokio_tungstenite as tokio_ws2;
 use tokio_ws2::tungstenite as ws2;
@@ -16,9 +17,16 @@ type WsRx = SplitStream<WsStream>;
 
 pub struct TokioConnection {
     source: EventSource,
+    lifecycle: LifecycleEmitter,
+    receive_notice: bool,
+    metadata: SharedMetadata,
     ws_rx: WsRx,
+    writer: futures::channel::mpsc::UnboundedSender<ws2::Message>,
     hb_handle: tokio::task::JoinHandle<()>,
+    buffer_size: usize,
     buffer: VecDeque<Result<Event, EventStreamError>>, // rx_handle: tokio::task::JoinHandle<()>,
+    /// 已经主动关闭，取完缓冲区后结束
+    closed: bool,
 }
 
 impl Stream for TokioConnection {
@@ -34,34 +42,34 @@ impl Stream for TokioConnection {
         if let Some(event) = self.buffer.pop_front() {
             return Ready(Some(event));
         }
+        if self.closed {
+            return Ready(None);
+        }
         // 读取新序列
         match self.ws_rx.poll_next_unpin(cx) {
             Ready(Some(Ok(Binary(bin)))) => {
-                let packet = RawPacket::from_buffer(&bin);
-                for data in packet.get_datas() {
-                    match data.into_event_data() {
-                        Ok(Some(event)) => {
-                            let source = self.source.clone();
-                            self.buffer.push_back(Ok(Event {
-                                data: event,
-                                meta: EventMeta::with_source(source),
-                            }))
-                        }
-                        Ok(None) => {}
-                        Err(e) => {
-                            log::warn!("解析数据包失败：{}", e);
-                        }
-                    }
-                }
+                self.handle_packet(RawPacket::from_buffer(&bin));
                 self.poll_next(cx)
             }
-            Ready(Some(Ok(Close(_)))) => Ready(Some(Err(ConnectionClosed))),
+            Ready(Some(Ok(Close(_)))) => {
+                self.lifecycle.emit(ConnectionState::ClosedByServer);
+                Ready(Some(Err(ConnectionClosed)))
+            }
             // 这不太可能发生，可能要标记一下
             Ready(Some(Ok(_))) => self.poll_next(cx),
             // 错误
-            Ready(Some(Err(e))) => Ready(Some(Err(WsError(e.to_string())))),
+            Ready(Some(Err(e))) => {
+                let reason = e.to_string();
+                self.lifecycle.emit(ConnectionState::Disconnected {
+                    reason: reason.clone(),
+                });
+                Ready(Some(Err(WsError(reason))))
+            }
             // 接受到None
-            Ready(None) => Ready(None),
+            Ready(None) => {
+                self.lifecycle.emit(ConnectionState::Closed);
+                Ready(None)
+            }
             Pending => Pending,
         }
     }
@@ -73,19 +81,73 @@ impl From<ws2::Error> for WsConnectError {
     }
 }
 use tokio::time::Duration;
-// 30s 发送一次心跳包
-const HB_RATE: Duration = Duration::from_secs(30);
+
+/// 在已经建立的连接上发送数据包，可以克隆
+///
+/// 数据包和心跳包由同一个任务按顺序发送，发送函数只负责放入队列，
+/// 连接关闭后返回[`EventStreamError::ConnectionClosed`]
+#[derive(Debug, Clone)]
+pub struct PacketSender {
+    writer: futures::channel::mpsc::UnboundedSender<ws2::Message>,
+}
+
+impl PacketSender {
+    pub fn send_raw(&self, packet: RawPacket<'_>) -> Result<(), EventStreamError> {
+        self.writer
+            .unbounded_send(ws2::Message::Binary(packet.ser()))
+            .map_err(|_| EventStreamError::ConnectionClosed)
+    }
+
+    pub fn send(&self, op: Operation, body: &[u8]) -> Result<(), EventStreamError> {
+        self.send_raw(RawPacket::build(op, body))
+    }
+
+    /// 包体序列化为json，比如重新鉴权时发送[`crate::Auth`]
+    pub fn send_json<T: serde::Serialize>(
+        &self,
+        op: Operation,
+        body: &T,
+    ) -> Result<(), EventStreamError> {
+        let body =
+            serde_json::to_vec(body).map_err(|e| EventStreamError::WsError(e.to_string()))?;
+        self.send(op, &body)
+    }
+
+    pub fn heartbeat(&self) -> Result<(), EventStreamError> {
+        self.send_raw(RawPacket::heartbeat())
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.writer.is_closed()
+    }
+}
 
 impl TokioConnection {
+    /// `auth_body` 为鉴权包的包体，一般是[`crate::Auth::ser`]的结果
     pub(crate) async fn connect(
         url: Url,
-        auth: Auth,
+        room_id: u64,
+        auth_body: Vec<u8>,
+        connector: &Connector,
+    ) -> Result<Self, WsConnectError> {
+        let handshake = Self::handshake(url, room_id, auth_body, connector);
+        match connector.config.connect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, handshake)
+                .await
+                .map_err(|_| WsConnectError::Timeout)?,
+            None => handshake.await,
+        }
+    }
+
+    async fn handshake(
+        url: Url,
+        room_id: u64,
+        auth_body: Vec<u8>,
         connector: &Connector,
     ) -> Result<Self, WsConnectError> {
         use ws2::Message::*;
-        let room_id = auth.roomid;
         let reqwest_req = connector
-            .client
+            .client()
             .inner()
             .request(Method::GET, url.clone())
             .build()
@@ -105,7 +167,7 @@ impl TokioConnection {
             .body(())
             .expect("shouldn't fail to build ssh req body");
         let (mut ws_stream, _resp) = tokio_ws2::connect_async(url.clone()).await?;
-        let authpack_bin = RawPacket::build(Operation::Auth, &auth.ser()).ser();
+        let authpack_bin = RawPacket::build(Operation::Auth, &auth_body).ser();
         ws_stream.send(Binary(authpack_bin)).await?;
         let resp = ws_stream.next().await.ok_or_else(|| {
             log::error!("ws stream encounter unexpected end");
@@ -113,39 +175,195 @@ impl TokioConnection {
         })??;
         match resp {
             Binary(auth_reply_bin) => {
-                log::debug!("auth reply: {:?}", RawPacket::from_buffer(&auth_reply_bin));
+                let auth_reply = RawPacket::from_buffer(&auth_reply_bin);
+                log::debug!("auth reply: {:?}", auth_reply);
+                if !auth_reply.is_auth_success() {
+                    log::error!("auth failed: {:?}", auth_reply);
+                    return Err(WsConnectError::AuthFailed);
+                }
             }
             _other => {
                 log::error!("auth reply is not a binary: {:?}", _other);
                 return Err(WsConnectError::AuthFailed);
             }
         }
+        let mut lifecycle = connector.emitter(url.host_str().unwrap_or_default());
+        lifecycle.emit(ConnectionState::Authenticated);
         let (mut tx, rx) = ws_stream.split();
-        // hb task
+        let (writer, writer_rx) = futures::channel::mpsc::unbounded::<ws2::Message>();
+        let hb_rate = connector.config.heartbeat_interval;
+        // hb task，同时负责发送其他消息，发送关闭帧后结束
         let hb = async move {
-            use tokio::time::*;
-            let mut interval = interval(HB_RATE);
-            loop {
-                interval.tick().await;
-                tx.send(ws2::Message::Binary(RawPacket::heartbeat().ser()))
-                    .await
-                    .expect("hb send error");
+            let heartbeat = futures_util::stream::unfold(
+                tokio::time::interval(hb_rate),
+                |mut interval| async move {
+                    interval.tick().await;
+                    Some((ws2::Message::Binary(RawPacket::heartbeat().ser()), interval))
+                },
+            );
+            let outgoing = futures_util::stream::select(heartbeat, writer_rx);
+            futures_util::pin_mut!(outgoing);
+            while let Some(message) = outgoing.next().await {
+                let is_close = message.is_close();
+                if let Err(e) = tx.send(message).await {
+                    log::warn!("ws send error: {}", e);
+                    break;
+                }
+                if is_close {
+                    break;
+                }
             }
         };
         Ok(TokioConnection {
-            source: EventSource {
-                room_id,
-                url,
-            },
+            source: EventSource { room_id, url },
+            lifecycle,
+            receive_notice: connector.receive_notice,
+            metadata: connector.metadata.clone(),
             ws_rx: rx,
+            writer,
             hb_handle: tokio::spawn(hb),
-            buffer: VecDeque::with_capacity(256),
+            buffer_size: connector.config.buffer_size,
+            buffer: VecDeque::with_capacity(connector.config.buffer_size),
+            closed: false,
         })
     }
 
+    /// 解析数据包，事件放入缓冲区
+    fn handle_packet(&mut self, packet: RawPacket<'_>) {
+        if packet.opcode() == Operation::HeartbeatReply as u32 {
+            self.lifecycle.emit(ConnectionState::HeartbeatOk);
+        }
+        for data in parse_packet(packet, self.receive_notice) {
+            let event = Event {
+                data,
+                meta: EventMeta::with_source(self.source.clone()),
+            };
+            if matches!(
+                event.data,
+                EventData::RoomChange(_)
+                    | EventData::LiveStartEvent(_)
+                    | EventData::LiveStopEvent(_)
+                    | EventData::StopLiveEvent(_)
+            ) {
+                let mut metadata = self.metadata.write().unwrap_or_else(|e| e.into_inner());
+                if let Some(metadata) = metadata.as_mut() {
+                    metadata.update(&event);
+                }
+            }
+            push_bounded(&mut self.buffer, Ok(event), self.buffer_size);
+        }
+    }
+
+    /// 在当前连接上切换到`connector`对应的直播间，`connector`需要已经初始化
+    ///
+    /// 服务器拒绝、连接已断开或者`timeout`内没有回复时，使用`connector`重新建立连接。
+    /// 返回`Ok(true)`表示在原连接上完成了切换，`Ok(false)`表示重新建立了连接
+    ///
+    /// 等待回复期间收到的旧直播间的事件仍然保留在缓冲区中，重新建立连接时也会转移到新的连接
+    ///
+    /// 重新建立连接也失败时返回错误，这时原连接可能已经切换了直播间，所以会被关闭：
+    /// 缓冲区中的事件仍然可以取出，之后事件流结束
+    pub async fn change_room(
+        &mut self,
+        connector: &Connector,
+        timeout: Duration,
+    ) -> Result<bool, ConnectError> {
+        use ws2::Message::*;
+        let from = self.source.room_id;
+        if self
+            .sender()
+            .send_json(Operation::ChangeRoom, &connector.auth())
+            .is_ok()
+        {
+            let wait = async {
+                loop {
+                    match self.ws_rx.next().await {
+                        Some(Ok(Binary(bin))) => {
+                            let packet = RawPacket::from_buffer(&bin);
+                            if packet.opcode() == Operation::ChangeRoomReply as u32 {
+                                log::debug!("change room reply: {:?}", packet);
+                                return packet.is_change_room_success();
+                            }
+                            self.handle_packet(packet);
+                        }
+                        Some(Ok(Close(_))) | Some(Err(_)) | None => return false,
+                        Some(Ok(_)) => {}
+                    }
+                }
+            };
+            match tokio::time::timeout(timeout, wait).await {
+                Ok(true) => {
+                    self.source.room_id = connector.roomid;
+                    self.receive_notice = connector.receive_notice;
+                    self.buffer_size = connector.config.buffer_size;
+                    self.metadata = connector.metadata.clone();
+                    self.lifecycle = connector.emitter(&self.lifecycle.host);
+                    self.lifecycle.emit(ConnectionState::RoomChanged { from });
+                    return Ok(true);
+                }
+                Ok(false) => log::warn!("change room {} -> {} refused", from, connector.roomid),
+                Err(_) => log::warn!("change room {} -> {} timeout", from, connector.roomid),
+            }
+        }
+        let mut connection = match connector.connect().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                self.shutdown();
+                return Err(e);
+            }
+        };
+        // 旧连接缓冲区中还没有取出的事件放在新连接的事件之前
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.append(&mut connection.buffer);
+        connection.buffer = buffer;
+        *self = connection;
+        Ok(false)
+    }
+
     pub fn abort(self) {
         drop(self)
     }
+
+    /// 发送关闭帧并停止读取，不等待服务器回复
+    fn shutdown(&mut self) {
+        let _ = self.writer.unbounded_send(ws2::Message::Close(None));
+        self.writer.close_channel();
+        self.closed = true;
+        self.lifecycle.emit(ConnectionState::Closed);
+    }
+
+    pub fn sender(&self) -> PacketSender {
+        PacketSender {
+            writer: self.writer.clone(),
+        }
+    }
+
+    /// 发送关闭帧，在`timeout`内等待服务器的关闭帧，然后停止心跳
+    ///
+    /// 返回缓冲区中和等待期间收到的事件
+    pub async fn close(mut self, timeout: Duration) -> Vec<Event> {
+        let mut events = Vec::new();
+        if self
+            .writer
+            .unbounded_send(ws2::Message::Close(None))
+            .is_ok()
+        {
+            let drain = async {
+                while let Some(item) = self.next().await {
+                    match item {
+                        Ok(event) => events.push(event),
+                        Err(_) => break,
+                    }
+                }
+            };
+            if tokio::time::timeout(timeout, drain).await.is_err() {
+                log::warn!("wait for close frame timeout");
+            }
+        }
+        events.extend(self.buffer.drain(..).filter_map(Result::ok));
+        self.lifecycle.emit(ConnectionState::Closed);
+        events
+    }
 }
 
 impl Drop for TokioConnection {
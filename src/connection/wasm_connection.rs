@@ -1,24 +1,33 @@
 use super::*;
 // use futures_util::{Stream as UtilSr, StreamExt};
-use futures::{stream::SplitStream, SinkExt, Stream, StreamExt};
+use futures::{
+    stream::{SplitSink, SplitStream},
+    SinkExt, Stream, StreamExt,
+};
 use gloo_net::{self, websocket::futures::WebSocket};
-use gloo_timers::future::IntervalStream;
+use gloo_timers::future::{IntervalStream, TimeoutFuture};
 use js_sys::Promise;
 use std::collections::VecDeque;
 
 // use tungstenite;
 use crate::{
     connection::WsConnectError,
-    event::Event,
-    packet::{Auth, Operation, RawPacket},
+    event::{Event, EventMeta, EventSource},
+    packet::{Operation, RawPacket},
+    Connector,
 };
+use reqwest::Url;
 use wasm_bindgen_futures::future_to_promise;
 // type WsStream = tokio_ws2::WebSocketStream<tokio_ws2::MaybeTlsStream<tokio::net::TcpStream>>;
 type WsRx = SplitStream<WebSocket>;
+type WsTx = SplitSink<WebSocket, gloo_net::websocket::Message>;
 
 pub struct WasmConnection {
+    source: EventSource,
+    receive_notice: bool,
     ws_rx: WsRx,
     pub hb_handle: Promise,
+    buffer_size: usize,
     buffer: VecDeque<Result<Event, EventStreamError>>, // rx_handle: tokio::task::JoinHandle<()>,
 }
 
@@ -40,10 +49,13 @@ impl Stream for WasmConnection {
         match self.ws_rx.poll_next_unpin(cx) {
             Ready(Some(Ok(Bytes(bin)))) => {
                 let packet = RawPacket::from_buffer(&bin);
-                for data in packet.get_datas() {
-                    if let Ok(Some(event)) = data.into_event() {
-                        self.buffer.push_back(Ok(event))
-                    }
+                for data in parse_packet(packet, self.receive_notice) {
+                    let event = Event {
+                        data,
+                        meta: EventMeta::with_source(self.source.clone()),
+                    };
+                    let limit = self.buffer_size;
+                    push_bounded(&mut self.buffer, Ok(event), limit);
                 }
                 self.poll_next(cx)
             }
@@ -71,39 +83,76 @@ impl From<gloo_net::websocket::WebSocketError> for WsConnectError {
     }
 }
 impl WasmConnection {
-    pub async fn connect(url: String, auth: Auth) -> Result<Self, WsConnectError> {
-        use gloo_net::websocket::Message::*;
-        let ws_stream = WebSocket::open(url.as_str())?;
-
-        let (mut tx, mut rx) = ws_stream.split();
-        let authpack_bin = RawPacket::build(Operation::Auth, auth.ser()).ser();
-        tx.send(Bytes(authpack_bin)).await?;
-        let _auth_reply = match rx.next().await {
-            Some(Ok(Bytes(auth_reply_bin))) => RawPacket::from_buffer(&auth_reply_bin),
-            _other => {
-                return Err(WsConnectError::UnexpecedEnd);
+    /// `auth_body` 为鉴权包的包体，一般是[`crate::Auth::ser`]的结果
+    pub(crate) async fn connect(
+        url: Url,
+        room_id: u64,
+        auth_body: Vec<u8>,
+        connector: &Connector,
+    ) -> Result<Self, WsConnectError> {
+        let handshake = Box::pin(Self::handshake(url.clone(), auth_body));
+        let (mut tx, rx) = match connector.config.connect_timeout {
+            Some(timeout) => {
+                let timeout =
+                    TimeoutFuture::new(timeout.as_millis().try_into().unwrap_or(u32::MAX));
+                match futures::future::select(handshake, timeout).await {
+                    futures::future::Either::Left((result, _)) => result?,
+                    futures::future::Either::Right(_) => return Err(WsConnectError::Timeout),
+                }
             }
+            None => handshake.await?,
         };
+        let hb_rate = connector
+            .config
+            .heartbeat_interval
+            .as_millis()
+            .try_into()
+            .unwrap_or(u32::MAX);
         // hb task
         let hb = async move {
-            // use tokio::time::*;
-            // 30s 发送一次
-            let mut interval = IntervalStream::new(30000);
+            let mut interval = IntervalStream::new(hb_rate);
             loop {
                 interval.next().await;
-                tx.send(Bytes(RawPacket::heartbeat().ser()))
-                    .await
-                    .expect("fail to send heart beat ");
+                tx.send(gloo_net::websocket::Message::Bytes(
+                    RawPacket::heartbeat().ser(),
+                ))
+                .await
+                .expect("fail to send heart beat ");
             }
         };
         // let hb = spawn_local();
         Ok(WasmConnection {
+            source: EventSource { room_id, url },
+            receive_notice: connector.receive_notice,
             ws_rx: rx,
             hb_handle: future_to_promise(hb),
-            buffer: VecDeque::with_capacity(256),
+            buffer_size: connector.config.buffer_size,
+            buffer: VecDeque::with_capacity(connector.config.buffer_size),
         })
     }
 
+    async fn handshake(url: Url, auth_body: Vec<u8>) -> Result<(WsTx, WsRx), WsConnectError> {
+        use gloo_net::websocket::Message::*;
+        let ws_stream = WebSocket::open(url.as_str())?;
+
+        let (mut tx, mut rx) = ws_stream.split();
+        let authpack_bin = RawPacket::build(Operation::Auth, &auth_body).ser();
+        tx.send(Bytes(authpack_bin)).await?;
+        match rx.next().await {
+            Some(Ok(Bytes(auth_reply_bin))) => {
+                let auth_reply = RawPacket::from_buffer(&auth_reply_bin);
+                if !auth_reply.is_auth_success() {
+                    log::error!("auth failed: {:?}", auth_reply);
+                    return Err(WsConnectError::AuthFailed);
+                }
+            }
+            _other => {
+                return Err(WsConnectError::UnexpecedEnd);
+            }
+        }
+        Ok((tx, rx))
+    }
+
     pub fn abort(self) {
         // literally do nothing
     }
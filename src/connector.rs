@@ -1,5 +1,7 @@
 use crate::{
+    api::DanmuInfoApi,
     connection::{synchub::SyncHub, *},
+    model::RoomMetadata,
     packet::*,
 };
 use bilibili_client::{
@@ -10,42 +12,417 @@ use bilibili_client::{
     reqwest_client::LoginInfo,
 };
 use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// 选择host的策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HostStrategy {
+    /// 从`host_index`开始依次尝试
+    #[default]
+    Sequential,
+    /// 只使用`host_index`指定的host
+    Fixed,
+    /// 从随机的位置开始依次尝试，用于分散多个连接的负载
+    Shuffle,
+}
+
+/// 连接参数
+///
+/// - `protover` 为3时使用brotli压缩，为0或1时不压缩，为2时使用zlib压缩（需要`deflate` feature）
+/// - `auth_type` 为鉴权包中的`type`字段
+#[derive(Debug, Clone)]
+pub struct ConnectConfig {
+    pub protover: i32,
+    pub platform: String,
+    pub auth_type: i32,
+    pub heartbeat_interval: Duration,
+    pub host_strategy: HostStrategy,
+    /// 连接单个host的超时时间，包括websocket握手和鉴权
+    pub connect_timeout: Option<Duration>,
+    /// 事件缓冲区最多保存的事件数量，超出时丢弃最早的事件
+    ///
+    /// 一个数据包可能包含多个事件，切换直播间时等待回复期间的事件也会保存在缓冲区中
+    pub buffer_size: usize,
+}
+
+impl Default for ConnectConfig {
+    fn default() -> Self {
+        ConnectConfig {
+            protover: 3,
+            platform: String::from("web"),
+            auth_type: 2,
+            heartbeat_interval: Duration::from_secs(30),
+            host_strategy: HostStrategy::default(),
+            connect_timeout: None,
+            buffer_size: 256,
+        }
+    }
+}
+
+/// [`Connector`]中可以持久化的部分，见[`Connector::state`]和[`ConnectorBuilder::state`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectorState {
+    pub roomid: u64,
+    pub uid: u64,
+    /// `None`表示鉴权时不使用key
+    #[serde(default)]
+    pub token: Option<String>,
+    pub host_list: Vec<Host>,
+}
+
+#[derive(Clone)]
+pub struct ConnectorBuilder {
+    roomid: u64,
+    login_info: LoginInfo,
+    client: Option<bilibili_client::reqwest_client::Client>,
+    receive_notice: bool,
+    config: ConnectConfig,
+    uid: Option<u64>,
+    resolve_room: bool,
+    /// 外层为`None`时需要请求`get_danmu_info`，内层为`None`表示不使用key
+    token: Option<Option<String>>,
+    host_list: Option<Vec<Host>>,
+    metadata: Option<RoomMetadata>,
+    fetch_metadata: bool,
+}
+
+/// 默认的弹幕服务器，不需要请求`get_danmu_info`
+pub const DEFAULT_HOST: &str = "broadcastlv.chat.bilibili.com";
+
+/// [`DEFAULT_HOST`]对应的[`Host`]
+pub fn default_host() -> Host {
+    Host {
+        host: String::from(DEFAULT_HOST),
+        port: 2243,
+        wss_port: 443,
+        ws_port: 2244,
+    }
+}
+
+/// 不请求http接口时缺少的部分，见[`ConnectorBuilder::build`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectorBuildError {
+    /// 需要请求`get_room_play_info`解析房间号，见[`ConnectorBuilder::resolve_room`]
+    RoomNotResolved,
+    /// 没有指定token，见[`ConnectorBuilder::token`]和[`ConnectorBuilder::anonymous`]
+    MissingToken,
+    /// 没有指定host列表
+    MissingHostList,
+}
+
+impl std::fmt::Display for ConnectorBuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectorBuildError::RoomNotResolved => write!(f, "房间号需要解析"),
+            ConnectorBuildError::MissingToken => write!(f, "没有指定token"),
+            ConnectorBuildError::MissingHostList => write!(f, "没有指定host列表"),
+        }
+    }
+}
+
+impl std::error::Error for ConnectorBuildError {}
+
+impl ConnectorBuilder {
+    pub fn new(roomid: u64) -> Self {
+        ConnectorBuilder {
+            roomid,
+            login_info: LoginInfo::default(),
+            client: None,
+            receive_notice: false,
+            config: ConnectConfig::default(),
+            uid: None,
+            resolve_room: true,
+            token: None,
+            host_list: None,
+            metadata: None,
+            fetch_metadata: false,
+        }
+    }
+
+    /// 鉴权时使用的uid，默认为`get_room_play_info`返回的主播uid，不解析房间号时为0
+    pub fn uid(mut self, uid: u64) -> Self {
+        self.uid = Some(uid);
+        self
+    }
+
+    /// 是否请求`get_room_play_info`把短号解析为长房间号，默认请求
+    ///
+    /// 不请求时房间号需要是长房间号
+    pub fn resolve_room(mut self, resolve_room: bool) -> Self {
+        self.resolve_room = resolve_room;
+        self
+    }
+
+    /// 同时指定token和host列表后不再请求`get_danmu_info`
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(Some(token.into()));
+        self
+    }
+
+    pub fn host_list(mut self, host_list: Vec<Host>) -> Self {
+        self.host_list = Some(host_list);
+        self
+    }
+
+    /// 使用保存的状态，之后不再请求http接口
+    pub fn state(mut self, state: ConnectorState) -> Self {
+        self.roomid = state.roomid;
+        self.token = Some(state.token);
+        self.uid(state.uid)
+            .resolve_room(false)
+            .host_list(state.host_list)
+    }
+
+    /// 匿名模式：uid为0，不使用token，连接[`DEFAULT_HOST`]
+    ///
+    /// 不需要请求任何http接口，但是房间号需要是长房间号，收到的用户信息可能不完整
+    pub fn anonymous(mut self) -> Self {
+        self.token = Some(None);
+        self.uid(0)
+            .resolve_room(false)
+            .host_list(vec![default_host()])
+    }
+
+    /// 指定后不再请求`get_room_metadata`
+    pub fn metadata(mut self, metadata: RoomMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// 初始化时是否请求`get_room_metadata`获取直播间信息，默认不请求
+    ///
+    /// 不请求时[`Connector::metadata`]为`None`，除非通过[`ConnectorBuilder::metadata`]指定
+    pub fn fetch_metadata(mut self, fetch_metadata: bool) -> Self {
+        self.fetch_metadata = fetch_metadata;
+        self
+    }
+
+    pub fn login_info(mut self, login_info: LoginInfo) -> Self {
+        self.login_info = login_info;
+        self
+    }
+
+    /// 使用自定义的客户端，代理、超时、UA等在构建客户端时设置，只作用于http接口
+    pub fn client(mut self, client: bilibili_client::reqwest_client::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn receive_notice(mut self, receive_notice: bool) -> Self {
+        self.receive_notice = receive_notice;
+        self
+    }
+
+    pub fn config(mut self, config: ConnectConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn protover(mut self, protover: i32) -> Self {
+        self.config.protover = protover;
+        self
+    }
+
+    pub fn platform(mut self, platform: impl Into<String>) -> Self {
+        self.config.platform = platform.into();
+        self
+    }
+
+    pub fn auth_type(mut self, auth_type: i32) -> Self {
+        self.config.auth_type = auth_type;
+        self
+    }
+
+    pub fn heartbeat_interval(mut self, heartbeat_interval: Duration) -> Self {
+        self.config.heartbeat_interval = heartbeat_interval;
+        self
+    }
+
+    pub fn host_strategy(mut self, host_strategy: HostStrategy) -> Self {
+        self.config.host_strategy = host_strategy;
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.config.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.config.buffer_size = buffer_size;
+        self
+    }
+
+    /// 请求http接口补全没有指定的uid、token和host列表
+    pub async fn init(mut self) -> bilibili_client::reqwest_client::ClientResult<Connector> {
+        let client = self.client.take().unwrap_or_default();
+        client.set_login_info(&self.login_info);
+        self.client = Some(client.clone());
+        self.init_with(client).await
+    }
+
+    /// 和[`ConnectorBuilder::init`]相同，但是通过`api`请求
+    ///
+    /// `api`会保存在连接器中，之后[`Connector::refresh`]也通过它请求，多个连接器共用时可以传入[`std::sync::Arc`]
+    pub async fn init_with<A>(mut self, api: A) -> Result<Connector, A::Error>
+    where
+        A: DanmuInfoApi + Send + Sync + 'static,
+    {
+        if self.resolve_room {
+            let RoomInfo { room_id, uid } = api.get_room_play_info(self.roomid).await?;
+            self.roomid = room_id;
+            self.uid.get_or_insert(uid);
+            self.resolve_room = false;
+        }
+        if self.token.is_none() || self.host_list.is_none() {
+            let DanmuInfoData { token, host_list } = api.get_danmu_info(self.roomid).await?;
+            self.token.get_or_insert(Some(token));
+            self.host_list.get_or_insert(host_list);
+        }
+        if self.fetch_metadata && self.metadata.is_none() {
+            self.metadata = api.get_room_metadata(self.roomid).await?;
+        }
+        let mut connector = self.finish();
+        connector.api = Some(crate::api::share(api));
+        Ok(connector)
+    }
+
+    /// 不请求http接口，房间号、token和host列表都需要已经确定，比如[`ConnectorBuilder::anonymous`]或者[`ConnectorBuilder::state`]
+    pub fn build(self) -> Result<Connector, ConnectorBuildError> {
+        if self.resolve_room {
+            return Err(ConnectorBuildError::RoomNotResolved);
+        }
+        if self.token.is_none() {
+            return Err(ConnectorBuildError::MissingToken);
+        }
+        if self.host_list.is_none() {
+            return Err(ConnectorBuildError::MissingHostList);
+        }
+        Ok(self.finish())
+    }
+
+    fn finish(self) -> Connector {
+        let client = std::sync::OnceLock::new();
+        if let Some(custom) = self.client {
+            custom.set_login_info(&self.login_info);
+            let _ = client.set(custom);
+        }
+        Connector {
+            client: std::sync::Arc::new(client),
+            api: None,
+            uid: self.uid.unwrap_or_default(),
+            host_index: 0,
+            roomid: self.roomid,
+            token: self.token.flatten(),
+            host_list: self.host_list.unwrap_or_default(),
+            login_info: self.login_info,
+            receive_notice: self.receive_notice,
+            config: self.config,
+            last_danmaku: Default::default(),
+            metadata: std::sync::Arc::new(std::sync::RwLock::new(self.metadata)),
+            lifecycle: None,
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Connector {
     pub roomid: u64,
     pub uid: u64,
-    pub token: String,
+    /// `None`表示鉴权时不使用key
+    pub token: Option<String>,
     pub host_index: usize,
     pub host_list: Vec<Host>,
     pub login_info: LoginInfo,
-    pub client: bilibili_client::reqwest_client::Client,
+    /// 见[`Connector::client`]，所有克隆共享
+    pub(crate) client: std::sync::Arc<std::sync::OnceLock<bilibili_client::reqwest_client::Client>>,
+    /// 初始化时使用的接口，没有时使用[`Connector::client`]
+    pub(crate) api: Option<crate::api::SharedDanmuInfoApi>,
+    /// 是否接收[`crate::event::NoticeEvent`]，这类通知比较多，默认不接收
+    pub receive_notice: bool,
+    pub config: ConnectConfig,
+    /// 这个账号在这个直播间上一次发送弹幕的时间，所有克隆共享，见[`Connector::send_danmaku`]
+    pub(crate) last_danmaku: crate::send::DanmakuSlot,
+    /// 直播间信息，通过这个连接器建立的连接会根据事件更新，所有克隆共享
+    pub(crate) metadata: SharedMetadata,
+    pub(crate) lifecycle: Option<LifecycleSender>,
 }
 
+pub(crate) type SharedMetadata = std::sync::Arc<std::sync::RwLock<Option<RoomMetadata>>>;
+
 impl Connector {
+    pub fn builder(roomid: u64) -> ConnectorBuilder {
+        ConnectorBuilder::new(roomid)
+    }
+
     pub async fn init(
-        mut roomid: u64,
+        roomid: u64,
         login_info: LoginInfo,
     ) -> bilibili_client::reqwest_client::ClientResult<Self> {
-        let client = bilibili_client::reqwest_client::Client::default();
-        client.set_login_info(&login_info);
-        let RoomInfo { room_id, uid } = client.get_room_play_info(roomid).await?;
-        roomid = room_id;
-        let DanmuInfoData { token, host_list } = client.get_danmu_info(room_id).await?;
-        let connector = Connector {
-            client,
-            uid,
-            host_index: 0,
-            roomid,
-            token,
-            host_list,
-            login_info,
-        };
-        Ok(connector)
+        Self::builder(roomid).login_info(login_info).init().await
+    }
+
+    /// 使用已有的客户端，多个直播间可以共用同一个客户端
+    pub async fn init_with_client(
+        roomid: u64,
+        login_info: LoginInfo,
+        client: bilibili_client::reqwest_client::Client,
+    ) -> bilibili_client::reqwest_client::ClientResult<Self> {
+        Self::builder(roomid)
+            .login_info(login_info)
+            .client(client)
+            .init()
+            .await
     }
 
+    /// 请求http接口使用的客户端，没有指定时在第一次使用时创建
+    pub fn client(&self) -> &bilibili_client::reqwest_client::Client {
+        self.client.get_or_init(|| {
+            let client = bilibili_client::reqwest_client::Client::default();
+            client.set_login_info(&self.login_info);
+            client
+        })
+    }
+
+    /// 登录信息中cookie的`bili_jct`，发送弹幕和房管操作需要，未登录时为`None`
+    pub fn csrf(&self) -> Option<&str> {
+        Some(self.login_info.bili_jct.as_str()).filter(|csrf| !csrf.is_empty())
+    }
+
+    /// 同时重置发送弹幕的间隔
     pub fn set_login_info(&mut self, login_info: LoginInfo) {
         self.login_info = login_info;
+        self.last_danmaku = Default::default();
+    }
+
+    pub fn set_receive_notice(&mut self, receive_notice: bool) {
+        self.receive_notice = receive_notice;
+    }
+
+    /// 初始化时获取的直播间信息，已经根据收到的事件更新，见[`ConnectorBuilder::fetch_metadata`]
+    pub fn metadata(&self) -> Option<RoomMetadata> {
+        self.metadata
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// 订阅连接状态，之后通过这个连接器（以及它的克隆）建立的连接都会发送状态变化
+    ///
+    /// 再次调用会替换之前的订阅
+    pub fn lifecycle(&mut self) -> LifecycleReceiver {
+        let (tx, rx) = futures::channel::mpsc::channel(LIFECYCLE_BUFFER_SIZE);
+        self.lifecycle = Some(tx);
+        rx
+    }
+
+    pub(crate) fn emitter(&self, host: &str) -> LifecycleEmitter {
+        LifecycleEmitter {
+            tx: self.lifecycle.clone(),
+            room_id: self.roomid,
+            host: host.to_owned(),
+        }
     }
 
     pub fn use_host(&mut self, index: usize) -> Result<&'_ str, usize> {
@@ -57,21 +434,136 @@ impl Connector {
         }
     }
 
+    /// 按照[`HostStrategy`]排列的host
+    pub(crate) fn hosts(&self) -> Vec<&Host> {
+        let len = self.host_list.len();
+        if len == 0 {
+            return vec![];
+        }
+        let start = match self.config.host_strategy {
+            HostStrategy::Sequential | HostStrategy::Fixed => self.host_index,
+            HostStrategy::Shuffle => {
+                use std::hash::{BuildHasher, Hasher};
+                let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+                hasher.write_u64(self.roomid);
+                hasher.finish() as usize
+            }
+        };
+        let take = match self.config.host_strategy {
+            HostStrategy::Fixed => 1,
+            _ => len,
+        };
+        (0..len)
+            .map(|offset| &self.host_list[(start % len + offset) % len])
+            .take(take)
+            .collect()
+    }
+
+    pub(crate) fn auth(&self) -> Auth {
+        Auth {
+            uid: self.uid,
+            roomid: self.roomid,
+            protover: self.config.protover,
+            platform: self.config.platform.clone(),
+            r#type: self.config.auth_type,
+            key: self.token.clone(),
+        }
+    }
+
+    /// 依次尝试host，鉴权失败时刷新token和host列表后再重试一次
+    ///
+    /// 刷新的结果不会保存到`self`，需要保存时使用[`Connector::connect_with_refresh`]
     pub async fn connect(&self) -> Result<Connection, ConnectError> {
+        self.clone().connect_with_refresh().await
+    }
+
+    /// 和[`Connector::connect`]相同，但是刷新的token和host列表会保存到`self`
+    ///
+    /// 不使用key的连接器（比如[`ConnectorBuilder::anonymous`]）不会刷新
+    pub async fn connect_with_refresh(&mut self) -> Result<Connection, ConnectError> {
+        match self.connect_hosts().await {
+            Err(ConnectError::AuthFailed) if self.token.is_some() => {
+                log::info!("auth failed, refreshing token of room {}", self.roomid);
+                self.refresh().await?;
+                let host = self.hosts().first().map(|host| host.host.clone());
+                self.emitter(&host.unwrap_or_default())
+                    .emit(ConnectionState::Reconnecting);
+                self.connect_hosts().await
+            }
+            result => result,
+        }
+    }
+
+    async fn connect_hosts(&self) -> Result<Connection, ConnectError> {
         if self.host_list.is_empty() {
             return Err(ConnectError::HostListIsEmpty);
         }
 
-        for host in &self.host_list {
+        let mut auth_failed = false;
+        let mut previous: Option<&str> = None;
+        for host in self.hosts() {
+            let mut emitter = self.emitter(&host.host);
+            if let Some(from) = previous {
+                emitter.emit(ConnectionState::HostSwitched {
+                    from: from.to_owned(),
+                });
+            }
+            previous = Some(&host.host);
+            emitter.emit(ConnectionState::Connecting);
             let url = host.wss();
-            let auth = Auth::new(self.uid, self.roomid, Some(self.token.clone()));
-            match Connection::connect(url, auth, self).await {
+            let auth = self.auth().ser();
+            match Connection::connect(url, self.roomid, auth, self).await {
                 Ok(stream) => return Ok(stream),
+                Err(WsConnectError::AuthFailed) => {
+                    log::warn!("connect error: auth failed");
+                    auth_failed = true;
+                }
                 Err(e) => log::warn!("connect error: {:?}", e),
             }
         }
         log::error!("connect error: all host failed");
-        Err(ConnectError::HandshakeError)
+        if auth_failed {
+            Err(ConnectError::AuthFailed)
+        } else {
+            Err(ConnectError::HandshakeError)
+        }
+    }
+
+    /// 重新获取token和host列表，token会过期，长期使用的连接器需要刷新
+    ///
+    /// 通过初始化时的[`DanmuInfoApi`]请求，没有时使用[`Connector::client`]
+    pub async fn refresh(&mut self) -> Result<(), ConnectError> {
+        let result = match self.api.clone() {
+            Some(api) => self.refresh_with(&*api).await,
+            None => {
+                let client = self.client().clone();
+                self.refresh_with(&client)
+                    .await
+                    .map_err(|e| format!("{e:?}"))
+            }
+        };
+        result.map_err(ConnectError::RefreshFailed)
+    }
+
+    pub async fn refresh_with<A: DanmuInfoApi + ?Sized>(
+        &mut self,
+        api: &A,
+    ) -> Result<(), A::Error> {
+        let DanmuInfoData { token, host_list } = api.get_danmu_info(self.roomid).await?;
+        self.token = Some(token);
+        self.host_list = host_list;
+        self.host_index = 0;
+        Ok(())
+    }
+
+    /// 可以持久化的状态，用于重启后不请求http接口直接连接
+    pub fn state(&self) -> ConnectorState {
+        ConnectorState {
+            roomid: self.roomid,
+            uid: self.uid,
+            token: self.token.clone(),
+            host_list: self.host_list.clone(),
+        }
     }
 
     pub async fn connect_all(&self) -> Result<SyncHub, ConnectError> {
@@ -80,17 +572,22 @@ impl Connector {
         }
 
         let mut hub = SyncHub::default();
-        for host in &self.host_list {
+        let mut connected = vec![];
+        for host in self.hosts() {
+            self.emitter(&host.host).emit(ConnectionState::Connecting);
             let url = host.wss();
-            let auth = Auth::new(self.uid, self.roomid, Some(self.token.clone()));
+            let auth = self.auth().ser();
 
-            match Connection::connect(url, auth, self).await {
+            match Connection::connect(url, self.roomid, auth, self).await {
                 Ok(stream) => {
                     hub.add_channel(stream.filter_map(|e| async { e.ok() }));
+                    connected.push(host.host.as_str());
                 }
                 Err(e) => log::warn!("connect error: {:?}", e),
             }
         }
+        // 同步通道的状态使用所有host
+        hub.lifecycle = Some(self.emitter(&connected.join(",")));
         if hub.channels.is_empty() {
             log::error!("connect error: all host failed");
             Err(ConnectError::HandshakeError)
@@ -104,5 +601,7 @@ impl Connector {
 pub enum ConnectError {
     HostListIsEmpty,
     HandshakeError,
+    AuthFailed,
+    RefreshFailed(String),
     WsError(String),
 }
@@ -0,0 +1,547 @@
+//! 补全事件中的用户信息
+//!
+//! 弹幕、上舰、进场等事件中的[`User::face`]和[`User::level`]经常为空，
+//! [`Enrich`]包装一个事件流，通过[`ProfileResolver`]批量查询缺失的信息并缓存。
+//! 等待查询的事件会被暂存，不影响其他事件的推送，所以事件的顺序可能改变。
+//! 暂存的事件数量有上限，等待超过[`EnrichConfig::max_wait`]的事件不再补全，直接推送
+//!
+//!```no_run,ignore
+//!let resolver = BiliProfileResolver::new(connector.client().inner().clone());
+//!let mut stream = connection
+//!    .filter_map(|evt| async { evt.ok() })
+//!    .enrich(resolver);
+//!while let Some(evt) = stream.next().await {
+//!    log::info!("{:?}", evt);
+//!}
+//!```
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures_util::{future::BoxFuture, FutureExt, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    event::{Event, EventData},
+    model::User,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Profile {
+    pub uid: u64,
+    pub uname: String,
+    pub face: String,
+    /// 查询不到等级时为`None`
+    pub level: Option<u64>,
+}
+
+impl Profile {
+    /// 只填充缺失的字段
+    pub fn fill(&self, user: &mut User) {
+        if user.uname.is_empty() {
+            user.uname.clone_from(&self.uname);
+        }
+        if user.face.is_none() {
+            user.face = Some(self.face.clone());
+        }
+        if user.level.is_none() {
+            user.level = self.level;
+        }
+    }
+}
+
+/// 批量查询用户信息，返回结果中缺少的uid视为查询不到
+pub trait ProfileResolver {
+    type Error: std::fmt::Debug;
+
+    fn resolve(&self, uids: Vec<u64>) -> BoxFuture<'_, Result<HashMap<u64, Profile>, Self::Error>>;
+
+    /// 是否能查询到[`Profile::level`]，不能时只为缺少头像的用户发起查询
+    fn resolves_level(&self) -> bool {
+        false
+    }
+}
+
+pub const USER_CARDS_URL: &str = "https://api.vc.bilibili.com/account/v1/user/cards";
+
+/// 通过`user/cards`接口批量查询
+#[derive(Debug, Clone)]
+pub struct BiliProfileResolver {
+    client: reqwest::Client,
+}
+
+impl BiliProfileResolver {
+    /// 可以使用[`crate::Connector::client`]的`inner()`
+    pub fn new(client: reqwest::Client) -> Self {
+        BiliProfileResolver { client }
+    }
+}
+
+#[derive(Deserialize)]
+struct CardsResponse {
+    code: i64,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    data: Vec<Card>,
+}
+
+#[derive(Deserialize)]
+struct Card {
+    mid: u64,
+    name: String,
+    face: String,
+    #[serde(default)]
+    level: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum ResolveError {
+    Http(reqwest::Error),
+    Api { code: i64, message: String },
+}
+
+impl ProfileResolver for BiliProfileResolver {
+    type Error = ResolveError;
+
+    fn resolve(&self, uids: Vec<u64>) -> BoxFuture<'_, Result<HashMap<u64, Profile>, Self::Error>> {
+        Box::pin(async move {
+            let uids = uids
+                .iter()
+                .map(u64::to_string)
+                .collect::<Vec<_>>()
+                .join(",");
+            let resp: CardsResponse = self
+                .client
+                .get(USER_CARDS_URL)
+                .query(&[("uids", uids)])
+                .send()
+                .await
+                .map_err(ResolveError::Http)?
+                .json()
+                .await
+                .map_err(ResolveError::Http)?;
+            if resp.code != 0 {
+                return Err(ResolveError::Api {
+                    code: resp.code,
+                    message: resp.message,
+                });
+            }
+            Ok(resp
+                .data
+                .into_iter()
+                .map(|card| {
+                    let profile = Profile {
+                        uid: card.mid,
+                        uname: card.name,
+                        face: card.face,
+                        level: card.level,
+                    };
+                    (profile.uid, profile)
+                })
+                .collect())
+        })
+    }
+
+    fn resolves_level(&self) -> bool {
+        true
+    }
+}
+
+/// - `capacity` 缓存的用户数量上限，超出时淘汰最久未使用的
+/// - `ttl` 缓存的有效期，查询不到的uid同样缓存
+/// - `batch_size` 一次查询的uid数量上限
+/// - `batch_delay` 第一个未命中的uid等待多久后发起查询，用于凑批
+/// - `min_interval` 两次查询的最小间隔
+/// - `resolve_timeout` 一次查询的超时时间，超时视为查询失败
+/// - `max_buffered` 暂存的事件数量上限，达到上限时暂停读取上游
+/// - `max_wait` 事件等待查询结果的最长时间，超时后不补全直接推送
+#[derive(Debug, Clone)]
+pub struct EnrichConfig {
+    pub capacity: usize,
+    pub ttl: Duration,
+    pub batch_size: usize,
+    pub batch_delay: Duration,
+    pub min_interval: Duration,
+    pub resolve_timeout: Duration,
+    pub max_buffered: usize,
+    pub max_wait: Duration,
+}
+
+impl Default for EnrichConfig {
+    fn default() -> Self {
+        EnrichConfig {
+            capacity: 4096,
+            ttl: Duration::from_secs(60 * 60),
+            batch_size: 50,
+            batch_delay: Duration::from_millis(200),
+            min_interval: Duration::from_secs(1),
+            resolve_timeout: Duration::from_secs(5),
+            max_buffered: 1024,
+            max_wait: Duration::from_secs(10),
+        }
+    }
+}
+
+/// LRU + TTL 缓存，同时记录查询不到的uid
+#[derive(Debug)]
+pub struct ProfileCache {
+    capacity: usize,
+    ttl: Duration,
+    tick: u64,
+    entries: HashMap<u64, (Option<Profile>, Instant, u64)>,
+    /// tick -> uid，最小的tick最久未使用
+    order: BTreeMap<u64, u64>,
+}
+
+impl ProfileCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        ProfileCache {
+            capacity: capacity.max(1),
+            ttl,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&mut self, uid: u64) -> Option<&Profile> {
+        self.lookup(uid).flatten()
+    }
+
+    /// 未缓存时为`None`，缓存了查询不到时为`Some(None)`
+    pub fn lookup(&mut self, uid: u64) -> Option<Option<&Profile>> {
+        let (_, inserted, tick) = self.entries.get(&uid)?;
+        let tick = *tick;
+        if inserted.elapsed() > self.ttl {
+            self.order.remove(&tick);
+            self.entries.remove(&uid);
+            return None;
+        }
+        self.tick += 1;
+        self.order.remove(&tick);
+        self.order.insert(self.tick, uid);
+        let entry = self.entries.get_mut(&uid)?;
+        entry.2 = self.tick;
+        Some(entry.0.as_ref())
+    }
+
+    pub fn insert(&mut self, profile: Profile) {
+        self.insert_entry(profile.uid, Some(profile));
+    }
+
+    /// 记录查询不到的uid，有效期内不再查询
+    pub fn insert_missing(&mut self, uid: u64) {
+        self.insert_entry(uid, None);
+    }
+
+    fn insert_entry(&mut self, uid: u64, profile: Option<Profile>) {
+        self.tick += 1;
+        if let Some((_, _, tick)) = self
+            .entries
+            .insert(uid, (profile, Instant::now(), self.tick))
+        {
+            self.order.remove(&tick);
+        }
+        self.order.insert(self.tick, uid);
+        while self.entries.len() > self.capacity {
+            let Some((_, uid)) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&uid);
+        }
+    }
+}
+
+/// 事件中可以补全的用户，`uid`为0的匿名用户除外
+fn user_mut(data: &mut EventData) -> Option<&mut User> {
+    let user = match data {
+        EventData::DanmakuEvent(evt) => &mut evt.user,
+        EventData::EnterRoomEvent(evt) => &mut evt.user,
+        EventData::FollowEvent(evt) => &mut evt.user,
+        EventData::ShareEvent(evt) => &mut evt.user,
+        EventData::BlindboxGiftEvent(evt) => &mut evt.user,
+        EventData::GiftEvent(evt) => &mut evt.user,
+        EventData::GuardBuyEvent(evt) => &mut evt.user,
+        EventData::SuperChatEvent(evt) => &mut evt.user,
+        EventData::GuardEnterRoomEvent(evt) => &mut evt.user,
+        EventData::LikeEvent(evt) => &mut evt.user,
+        EventData::UserBlockedEvent(evt) => &mut evt.user,
+        EventData::RedPocketStartEvent(evt) => &mut evt.sender,
+        _ => return None,
+    };
+    (user.uid != 0).then_some(user)
+}
+
+/// 需要查询的uid，`level`只在能查询到时才需要补全
+fn missing_uid(event: &mut Event, resolves_level: bool) -> Option<u64> {
+    user_mut(&mut event.data)
+        .filter(|user| user.face.is_none() || (resolves_level && user.level.is_none()))
+        .map(|user| user.uid)
+}
+
+type ResolveFuture = BoxFuture<'static, (Vec<u64>, Option<HashMap<u64, Profile>>)>;
+
+/// 见[`EnrichExt::enrich`]
+pub struct Enrich<S, R> {
+    inner: Option<S>,
+    resolver: Arc<R>,
+    config: EnrichConfig,
+    cache: ProfileCache,
+    resolves_level: bool,
+    ready: VecDeque<Event>,
+    /// 等待查询结果的事件，以及加入的时间，按加入顺序排列
+    waiting: VecDeque<(u64, Instant, Event)>,
+    /// 等待发起查询的uid，以及第一个uid加入的时间
+    queue: Vec<u64>,
+    queued_at: Option<Instant>,
+    /// 已经在队列中或者正在查询的uid
+    requested: HashSet<u64>,
+    in_flight: Option<ResolveFuture>,
+    last_request: Option<Instant>,
+    timer: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+impl<S, R> Enrich<S, R>
+where
+    R: ProfileResolver + Send + Sync + 'static,
+{
+    pub fn new(inner: S, resolver: R, config: EnrichConfig) -> Self {
+        Enrich {
+            inner: Some(inner),
+            resolves_level: resolver.resolves_level(),
+            resolver: Arc::new(resolver),
+            cache: ProfileCache::new(config.capacity, config.ttl),
+            config,
+            ready: VecDeque::new(),
+            waiting: VecDeque::new(),
+            queue: Vec::new(),
+            queued_at: None,
+            requested: HashSet::new(),
+            in_flight: None,
+            last_request: None,
+            timer: None,
+        }
+    }
+
+    pub fn cache(&self) -> &ProfileCache {
+        &self.cache
+    }
+
+    /// 暂存的事件是否达到上限
+    fn is_full(&self) -> bool {
+        self.ready.len() + self.waiting.len() >= self.config.max_buffered.max(1)
+    }
+
+    fn accept(&mut self, mut event: Event) {
+        let Some(uid) = missing_uid(&mut event, self.resolves_level) else {
+            self.ready.push_back(event);
+            return;
+        };
+        if let Some(profile) = self.cache.lookup(uid) {
+            if let (Some(profile), Some(user)) = (profile, user_mut(&mut event.data)) {
+                profile.fill(user);
+            }
+            self.ready.push_back(event);
+            return;
+        }
+        if self.requested.insert(uid) {
+            self.queue.push(uid);
+            self.queued_at.get_or_insert_with(Instant::now);
+        }
+        self.waiting.push_back((uid, Instant::now(), event));
+    }
+
+    fn finish(&mut self, uids: Vec<u64>, profiles: Option<HashMap<u64, Profile>>) {
+        for uid in &uids {
+            self.requested.remove(uid);
+        }
+        // 查询失败时不缓存，下次遇到再查询
+        let profiles = match profiles {
+            Some(profiles) => {
+                for uid in &uids {
+                    match profiles.get(uid) {
+                        Some(profile) => self.cache.insert(profile.clone()),
+                        None => self.cache.insert_missing(*uid),
+                    }
+                }
+                profiles
+            }
+            None => HashMap::new(),
+        };
+        let (done, waiting): (VecDeque<_>, VecDeque<_>) = std::mem::take(&mut self.waiting)
+            .into_iter()
+            .partition(|(uid, _, _)| uids.contains(uid));
+        self.waiting = waiting;
+        for (uid, _, mut event) in done {
+            if let (Some(profile), Some(user)) = (profiles.get(&uid), user_mut(&mut event.data)) {
+                profile.fill(user);
+            }
+            // 查询失败也照常推送
+            self.ready.push_back(event);
+        }
+    }
+
+    /// 等待超过`max_wait`的事件不再补全，直接推送
+    fn expire(&mut self, now: Instant) {
+        while let Some((_, at, _)) = self.waiting.front() {
+            if now < *at + self.config.max_wait {
+                break;
+            }
+            if let Some((_, _, event)) = self.waiting.pop_front() {
+                self.ready.push_back(event);
+            }
+        }
+    }
+
+    /// 下一次需要唤醒的时间，发起查询或者有事件等待超时
+    fn next_wake_at(&self) -> Option<Instant> {
+        let request = self
+            .in_flight
+            .is_none()
+            .then(|| self.next_request_at())
+            .flatten();
+        let expire = self
+            .waiting
+            .front()
+            .map(|(_, at, _)| *at + self.config.max_wait);
+        match (request, expire) {
+            (Some(request), Some(expire)) => Some(request.min(expire)),
+            (request, expire) => request.or(expire),
+        }
+    }
+
+    /// 可以发起下一次查询的时间
+    fn next_request_at(&self) -> Option<Instant> {
+        let queued_at = self.queued_at?;
+        let rate_limit = self
+            .last_request
+            .map(|last| last + self.config.min_interval);
+        let batch = if self.queue.len() >= self.config.batch_size
+            // 上游已经结束，不需要再凑批
+            || self.inner.is_none()
+        {
+            queued_at
+        } else {
+            queued_at + self.config.batch_delay
+        };
+        Some(rate_limit.map_or(batch, |rate_limit| rate_limit.max(batch)))
+    }
+
+    fn start_request(&mut self) {
+        let take = self.config.batch_size.max(1).min(self.queue.len());
+        let uids: Vec<u64> = self.queue.drain(..take).collect();
+        self.queued_at = (!self.queue.is_empty()).then(Instant::now);
+        self.last_request = Some(Instant::now());
+        let resolver = self.resolver.clone();
+        let timeout = self.config.resolve_timeout;
+        self.in_flight = Some(
+            async move {
+                let result = tokio::time::timeout(timeout, resolver.resolve(uids.clone())).await;
+                match result {
+                    Ok(Ok(profiles)) => (uids, Some(profiles)),
+                    Ok(Err(e)) => {
+                        log::warn!("resolve profiles error: {:?}", e);
+                        (uids, None)
+                    }
+                    Err(_) => {
+                        log::warn!("resolve profiles timeout: {:?}", uids);
+                        (uids, None)
+                    }
+                }
+            }
+            .boxed(),
+        );
+    }
+}
+
+impl<S, R> Stream for Enrich<S, R>
+where
+    S: Stream<Item = Event> + Unpin,
+    R: ProfileResolver + Send + Sync + 'static,
+{
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        // 达到上限时不再读取上游，推送出事件后调用方会再次poll
+        while !this.is_full() {
+            let Some(inner) = this.inner.as_mut() else {
+                break;
+            };
+            match inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(event)) => this.accept(event),
+                Poll::Ready(None) => this.inner = None,
+                Poll::Pending => break,
+            }
+        }
+        loop {
+            if let Some(in_flight) = this.in_flight.as_mut() {
+                if let Poll::Ready((uids, profiles)) = in_flight.poll_unpin(cx) {
+                    this.in_flight = None;
+                    this.finish(uids, profiles);
+                }
+            }
+            let now = Instant::now();
+            this.expire(now);
+            if this.in_flight.is_none() && this.next_request_at().is_some_and(|at| at <= now) {
+                this.timer = None;
+                this.start_request();
+                continue;
+            }
+            let Some(at) = this.next_wake_at() else {
+                break;
+            };
+            let deadline = tokio::time::Instant::from_std(at);
+            let timer = this
+                .timer
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+            if timer.deadline() != deadline {
+                timer.as_mut().reset(deadline);
+            }
+            match timer.as_mut().poll(cx) {
+                Poll::Ready(()) => this.timer = None,
+                Poll::Pending => break,
+            }
+        }
+        if let Some(event) = this.ready.pop_front() {
+            Poll::Ready(Some(event))
+        } else if this.inner.is_none() && this.waiting.is_empty() {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+pub trait EnrichExt: Stream<Item = Event> + Sized {
+    /// 使用默认的[`EnrichConfig`]补全用户信息
+    fn enrich<R>(self, resolver: R) -> Enrich<Self, R>
+    where
+        R: ProfileResolver + Send + Sync + 'static,
+    {
+        Enrich::new(self, resolver, EnrichConfig::default())
+    }
+
+    fn enrich_with<R>(self, resolver: R, config: EnrichConfig) -> Enrich<Self, R>
+    where
+        R: ProfileResolver + Send + Sync + 'static,
+    {
+        Enrich::new(self, resolver, config)
+    }
+}
+
+impl<S: Stream<Item = Event>> EnrichExt for S {}
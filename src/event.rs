@@ -4,8 +4,15 @@ use crate::model::*;
 
 use serde::{Deserialize, Serialize};
 use std::hash::Hash;
+
+/// [`EventData`]中的具体事件类型
+pub trait EventType: Sized {
+    fn from_data(data: &EventData) -> Option<&Self>;
+}
+
 macro_rules! define_event {
     ($(
+        $(#[$struct_attrs:meta])*
         $name:ident{$(
             $(#[$attrs:meta])*
             $arg:ident: $ty:ty
@@ -17,7 +24,20 @@ macro_rules! define_event {
             $($name ($name)),*
         }
 
+        impl EventData {
+            /// 所有事件类型名
+            pub const NAMES: &'static [&'static str] = &[$(stringify!($name)),*];
+
+            /// 事件类型名，与序列化时的`cmd`字段相同
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(EventData::$name(_) => stringify!($name)),*
+                }
+            }
+        }
+
         $(
+            $(#[$struct_attrs])*
             #[derive(Clone, Debug, Serialize, Deserialize, Hash)]
             pub struct $name {
                 $(
@@ -30,22 +50,78 @@ macro_rules! define_event {
                     EventData::$name(event)
                 }
             }
+            impl EventType for $name {
+                fn from_data(data: &EventData) -> Option<&Self> {
+                    match data {
+                        EventData::$name(event) => Some(event),
+                        _ => None,
+                    }
+                }
+            }
         )*
     };
 }
 
 define_event! {
     DanmakuEvent {
-        /// 第一位：是否是抽奖弹幕，2~4位，舰长类型
-        flag: u64,
+        /// 弹幕id，[`DanmakuRecallEvent`]通过它指明被撤回的弹幕，旧版协议中没有这个字段
+        #[serde(default)]
+        id: Option<String>,
+        flag: DanmakuFlags,
         message: DanmakuMessage,
         user: User,
         fans_medal: Option<FansMedal>,
         ts: u64,
     },
+    DanmakuRecallEvent {
+        /// 被撤回弹幕的[`DanmakuEvent::id`]
+        id: String,
+    },
+    /// 用户被房管或者主播禁言
+    UserBlockedEvent {
+        user: User,
+        /// 1为房管，2为主播
+        operator: u64,
+    },
+    RoomSilentOnEvent {
+        kind: SilenceKind,
+        level: u64,
+        /// 结束时间的时间戳，-1为本场直播结束
+        second: i64,
+    },
+    RoomSilentOffEvent {},
+    /// 天选时刻、红包等活动中被折叠的重复弹幕
+    DanmakuAggregationEvent {
+        /// 活动id，比如[`AnchorLotteryStartEvent::id`]或者[`RedPocketStartEvent::lot_id`]
+        activity_identity: String,
+        /// 1为天选时刻，2为红包
+        activity_source: u64,
+        message: String,
+        /// 被折叠的弹幕数量
+        num: u64,
+        timestamp: u64,
+    },
     EnterRoomEvent {
         user: User,
-        fans_medal: Option<FansMedal>
+        fans_medal: Option<FansMedal>,
+        #[serde(default)]
+        timestamp: u64,
+        /// 用于排序的分数
+        #[serde(default)]
+        score: u64,
+    },
+    FollowEvent {
+        kind: FollowKind,
+        user: User,
+        fans_medal: Option<FansMedal>,
+        timestamp: u64,
+        score: u64,
+    },
+    ShareEvent {
+        user: User,
+        fans_medal: Option<FansMedal>,
+        timestamp: u64,
+        score: u64,
     },
     BlindboxGiftEvent {
         user: User,
@@ -61,14 +137,15 @@ define_event! {
         rnd: String,
     },
     GuardBuyEvent {
-        level: u64,
-        price: u64,
+        level: GuardLevel,
+        price: Money,
         user: User
     },
     SuperChatEvent {
         user: User,
         fans_medal: Option<FansMedal>,
-        price: u64,
+        #[serde(deserialize_with = "Money::deserialize_cny_compat")]
+        price: Money,
         message: String,
         message_jpn: Option<String>
     },
@@ -95,6 +172,16 @@ define_event! {
     OnlineRankCountEvent {
         count: u64,
     },
+    /// 开播，`LIVE`
+    LiveStartEvent {
+        room_id: u64,
+        /// 开播时间的时间戳，旧版协议中没有这个字段
+        live_time: u64,
+    },
+    /// 下播，`PREPARING`
+    LiveStopEvent {
+        room_id: u64,
+    },
     StopLiveEvent{
         room_id_list: Vec<u64>
     },
@@ -107,6 +194,107 @@ define_event! {
         sub_session_key: String,
         title: String,
     },
+    AnchorLotteryStartEvent {
+        id: u64,
+        award_name: String,
+        award_num: u64,
+        award_image: String,
+        /// 参与抽奖需要发送的弹幕，为空则无需发送
+        danmu: String,
+        /// 参与条件的描述，比如“当前主播粉丝勋章至少1级”
+        require_text: String,
+        gift: Option<LotteryGiftRequirement>,
+        /// 抽奖总时长，单位为秒
+        max_time: u64,
+        /// 剩余时长，单位为秒
+        time: u64,
+        current_time: u64,
+    },
+    /// 天选时刻的审核结果
+    AnchorLotteryCheckEvent {
+        id: u64,
+        /// 审核状态，4为通过
+        status: u64,
+        /// 未通过的原因
+        reject_reason: String,
+    },
+    AnchorLotteryAwardEvent {
+        id: u64,
+        award_name: String,
+        award_num: u64,
+        award_image: String,
+        winners: Vec<User>,
+    },
+    RedPocketStartEvent {
+        lot_id: u64,
+        sender: User,
+        /// 参与红包抽奖需要发送的弹幕
+        danmu: String,
+        awards: Vec<RedPocketAward>,
+        /// 红包总价值
+        total_price: Money,
+        start_time: u64,
+        end_time: u64,
+    },
+    RedPocketWinnerEvent {
+        lot_id: u64,
+        total_num: u64,
+        winners: Vec<RedPocketWinner>,
+    },
+    EntryEffectEvent {
+        uid: u64,
+        face: String,
+        /// 进场特效的文案，用户名被`<%`和`%>`包裹
+        copy_writing: String,
+        privilege_type: GuardLevel,
+    },
+    NoticeEvent {
+        kind: NoticeKind,
+        /// 高亮的部分被`<%`和`%>`包裹
+        message: String,
+        /// 通知相关的直播间，比如送出礼物的直播间
+        room_id: Option<u64>,
+        link_url: Option<String>,
+    },
+    LikeEvent {
+        user: User,
+        fans_medal: Option<FansMedal>,
+        like_text: String,
+    },
+    LikeCountUpdateEvent {
+        /// 本场直播的累计点赞数
+        click_count: u64,
+    },
+    PkStartEvent {
+        pk_id: u64,
+        /// 发起方
+        init_info: PkSide,
+        /// 匹配方
+        match_info: PkSide,
+        start_time: u64,
+        /// 进入结算前的冻结时间
+        frozen_time: u64,
+        end_time: u64,
+    },
+    PkProgressEvent {
+        pk_id: u64,
+        init_info: PkSide,
+        match_info: PkSide,
+    },
+    PkEndEvent {
+        pk_id: u64,
+        init_info: PkSide,
+        match_info: PkSide,
+        /// 胜方的房间号，平局为`None`
+        winner: Option<u64>,
+    },
+    PkSettleEvent {
+        pk_id: u64,
+        /// 本房间的结果，1为胜利，-1为失败，其他为平局
+        result_type: i64,
+        /// 胜方的房间号，平局为`None`
+        winner: Option<u64>,
+    },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -149,6 +337,54 @@ impl EventMeta {
     }
 }
 
+impl EventData {
+    /// 事件的发起用户
+    pub fn user(&self) -> Option<&User> {
+        match self {
+            EventData::DanmakuEvent(evt) => Some(&evt.user),
+            EventData::EnterRoomEvent(evt) => Some(&evt.user),
+            EventData::FollowEvent(evt) => Some(&evt.user),
+            EventData::ShareEvent(evt) => Some(&evt.user),
+            EventData::BlindboxGiftEvent(evt) => Some(&evt.user),
+            EventData::GiftEvent(evt) => Some(&evt.user),
+            EventData::GuardBuyEvent(evt) => Some(&evt.user),
+            EventData::SuperChatEvent(evt) => Some(&evt.user),
+            EventData::GuardEnterRoomEvent(evt) => Some(&evt.user),
+            EventData::LikeEvent(evt) => Some(&evt.user),
+            EventData::UserBlockedEvent(evt) => Some(&evt.user),
+            EventData::RedPocketStartEvent(evt) => Some(&evt.sender),
+            _ => None,
+        }
+    }
+
+    /// 发起用户佩戴的粉丝勋章
+    pub fn fans_medal(&self) -> Option<&FansMedal> {
+        match self {
+            EventData::DanmakuEvent(evt) => evt.fans_medal.as_ref(),
+            EventData::EnterRoomEvent(evt) => evt.fans_medal.as_ref(),
+            EventData::FollowEvent(evt) => evt.fans_medal.as_ref(),
+            EventData::ShareEvent(evt) => evt.fans_medal.as_ref(),
+            EventData::BlindboxGiftEvent(evt) => evt.fans_medal.as_ref(),
+            EventData::GiftEvent(evt) => evt.fans_medal.as_ref(),
+            EventData::SuperChatEvent(evt) => evt.fans_medal.as_ref(),
+            EventData::LikeEvent(evt) => evt.fans_medal.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// 事件涉及的金额，包括礼物、醒目留言、大航海和红包
+    pub fn value(&self) -> Option<Money> {
+        match self {
+            EventData::BlindboxGiftEvent(evt) => Some(evt.gift.value()),
+            EventData::GiftEvent(evt) => Some(evt.gift.value()),
+            EventData::GuardBuyEvent(evt) => Some(evt.price),
+            EventData::SuperChatEvent(evt) => Some(evt.price),
+            EventData::RedPocketStartEvent(evt) => Some(evt.total_price),
+            _ => None,
+        }
+    }
+}
+
 impl Event {
     pub fn is_stop_live(&self) -> bool {
         if let EventData::StopLiveEvent(StopLiveEvent { room_id_list }) = &self.data {
@@ -0,0 +1,942 @@
+//! 事件过滤器
+//!
+//! 可以在代码中构造，也可以从字符串解析，方便写进配置文件，不需要重新部署就能调整过滤条件
+//!
+//!```no_run,ignore
+//!let filter: EventFilter = "kind in [danmaku, gift] && medal.level >= 10".parse()?;
+//!let mut stream = connection.filter_map(|evt| async { evt.ok() }).filter_events(filter);
+//!while let Some(evt) = stream.next().await {
+//!    // 处理事件
+//!}
+//!```
+//!
+//! # 语法
+//! - 比较：`字段 运算符 值`，运算符为`==`、`!=`、`>`、`>=`、`<`、`<=`
+//! - `字段 in [值, ...]`，`字段 contains "文本"`
+//! - 组合：`&&`、`||`、`!`和括号，`&&`优先于`||`；`true`和`false`
+//! - 值：整数；金额，比如`10cny`、`1.5cny`、`100gold`、`5battery`；字符串`"..."`；
+//!   大航海等级`governor`、`admiral`、`captain`、`none`
+//! - `kind`的值为事件类型，不区分大小写，可以省略`Event`后缀和下划线，比如`danmaku`、`super_chat`
+//! - 事件不包含某个字段时，关于这个字段的条件都为假
+//!
+//! 字段见[`Field`]。在toml中直接写成字符串：
+//!```toml
+//!filter = "kind == super_chat || gift.value >= 10cny"
+//!```
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+};
+
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    event::{Event, EventData},
+    model::{DanmakuMessage, GuardLevel, Money},
+};
+
+/// 可以过滤的字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    /// `kind`，事件类型
+    Kind,
+    /// `room`，事件来源的房间号
+    Room,
+    /// `user.uid`
+    Uid,
+    /// `user.name`
+    Uname,
+    /// `user.level`，需要先补全用户信息
+    UserLevel,
+    /// `medal.level`
+    MedalLevel,
+    /// `medal.name`
+    MedalName,
+    /// `medal.room`，粉丝勋章所属的房间号
+    MedalRoom,
+    /// `guard`，粉丝勋章上的大航海等级，或者购买的大航海等级，总督最高
+    Guard,
+    /// `gift.name`
+    GiftName,
+    /// `gift.id`
+    GiftId,
+    /// `gift.num`
+    GiftNum,
+    /// `gift.value`，礼物总价值，银瓜子礼物为0
+    GiftValue,
+    /// `value`，事件涉及的金额，见[`EventData::value`]
+    Value,
+    /// `message`，弹幕或者醒目留言的内容
+    Message,
+}
+
+const FIELDS: &[(&str, Field)] = &[
+    ("kind", Field::Kind),
+    ("room", Field::Room),
+    ("user.uid", Field::Uid),
+    ("user.name", Field::Uname),
+    ("user.level", Field::UserLevel),
+    ("medal.level", Field::MedalLevel),
+    ("medal.name", Field::MedalName),
+    ("medal.room", Field::MedalRoom),
+    ("guard", Field::Guard),
+    ("gift.name", Field::GiftName),
+    ("gift.id", Field::GiftId),
+    ("gift.num", Field::GiftNum),
+    ("gift.value", Field::GiftValue),
+    ("value", Field::Value),
+    ("message", Field::Message),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldType {
+    Kind,
+    Number,
+    Money,
+    Text,
+    Guard,
+}
+
+impl Field {
+    pub fn name(self) -> &'static str {
+        FIELDS
+            .iter()
+            .find(|(_, field)| *field == self)
+            .map(|(name, _)| *name)
+            .unwrap_or_default()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        FIELDS
+            .iter()
+            .find(|(field_name, _)| *field_name == name)
+            .map(|(_, field)| *field)
+    }
+
+    fn ty(self) -> FieldType {
+        use Field::*;
+        match self {
+            Kind => FieldType::Kind,
+            Room | Uid | UserLevel | MedalLevel | MedalRoom | GiftId | GiftNum => FieldType::Number,
+            GiftValue | Value => FieldType::Money,
+            Uname | MedalName | GiftName | Message => FieldType::Text,
+            Guard => FieldType::Guard,
+        }
+    }
+
+    fn get(self, event: &Event) -> Option<FieldValue<'_>> {
+        let data = &event.data;
+        let value = match self {
+            Field::Kind => FieldValue::Text(data.name()),
+            Field::Room => FieldValue::Number(event.meta.source.as_ref()?.room_id),
+            Field::Uid => FieldValue::Number(data.user()?.uid),
+            Field::Uname => FieldValue::Text(&data.user()?.uname),
+            Field::UserLevel => FieldValue::Number(data.user()?.level?),
+            Field::MedalLevel => FieldValue::Number(data.fans_medal()?.medal_level),
+            Field::MedalName => FieldValue::Text(&data.fans_medal()?.medal_name),
+            Field::MedalRoom => FieldValue::Number(data.fans_medal()?.anchor_roomid),
+            Field::Guard => match data {
+                EventData::GuardBuyEvent(evt) => FieldValue::Guard(evt.level),
+                data => FieldValue::Guard(data.fans_medal()?.guard_level),
+            },
+            Field::GiftName => FieldValue::Text(&gift(data)?.gift_name),
+            Field::GiftId => FieldValue::Number(gift(data)?.gift_id),
+            Field::GiftNum => FieldValue::Number(gift(data)?.num),
+            Field::GiftValue => FieldValue::Money(gift(data)?.value()),
+            Field::Value => FieldValue::Money(data.value()?),
+            Field::Message => match data {
+                EventData::DanmakuEvent(evt) => match &evt.message {
+                    DanmakuMessage::Plain { message } => FieldValue::Text(message),
+                    DanmakuMessage::Emoticon { alt_message, .. } => FieldValue::Text(alt_message),
+                },
+                EventData::SuperChatEvent(evt) => FieldValue::Text(&evt.message),
+                _ => return None,
+            },
+        };
+        Some(value)
+    }
+
+    /// 大航海等级字段也接受协议中的数字
+    fn accept(self, literal: Literal) -> Option<Literal> {
+        match (self.ty(), literal) {
+            (FieldType::Guard, Literal::Number(level @ 0..=3)) => {
+                Some(Literal::Guard(GuardLevel::from(level)))
+            }
+            (FieldType::Number, literal @ Literal::Number(_))
+            | (FieldType::Money, literal @ Literal::Money(_))
+            | (FieldType::Text | FieldType::Kind, literal @ Literal::Text(_))
+            | (FieldType::Guard, literal @ Literal::Guard(_)) => Some(literal),
+            _ => None,
+        }
+    }
+
+    pub fn eq(self, value: impl Into<Literal>) -> EventFilter {
+        EventFilter::compare(self, CmpOp::Eq, value)
+    }
+
+    pub fn ne(self, value: impl Into<Literal>) -> EventFilter {
+        EventFilter::compare(self, CmpOp::Ne, value)
+    }
+
+    pub fn gt(self, value: impl Into<Literal>) -> EventFilter {
+        EventFilter::compare(self, CmpOp::Gt, value)
+    }
+
+    pub fn ge(self, value: impl Into<Literal>) -> EventFilter {
+        EventFilter::compare(self, CmpOp::Ge, value)
+    }
+
+    pub fn lt(self, value: impl Into<Literal>) -> EventFilter {
+        EventFilter::compare(self, CmpOp::Lt, value)
+    }
+
+    pub fn le(self, value: impl Into<Literal>) -> EventFilter {
+        EventFilter::compare(self, CmpOp::Le, value)
+    }
+
+    pub fn one_of<I, L>(self, values: I) -> EventFilter
+    where
+        I: IntoIterator<Item = L>,
+        L: Into<Literal>,
+    {
+        EventFilter::In {
+            field: self,
+            values: values.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn contains(self, text: impl Into<String>) -> EventFilter {
+        EventFilter::Contains {
+            field: self,
+            text: text.into(),
+        }
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+fn gift(data: &EventData) -> Option<&crate::model::Gift> {
+    match data {
+        EventData::GiftEvent(evt) => Some(&evt.gift),
+        EventData::BlindboxGiftEvent(evt) => Some(&evt.gift),
+        _ => None,
+    }
+}
+
+/// 事件类型名是否匹配，忽略大小写、下划线和`Event`后缀
+fn kind_eq(name: &str, kind: &str) -> bool {
+    fn key(s: &str) -> impl Iterator<Item = char> + '_ {
+        s.strip_suffix("Event")
+            .unwrap_or(s)
+            .chars()
+            .filter(|c| *c != '_')
+            .map(|c| c.to_ascii_lowercase())
+    }
+    key(name).eq(key(kind))
+}
+
+/// 大航海等级的高低，总督最高
+fn guard_rank(level: GuardLevel) -> u8 {
+    match level {
+        GuardLevel::None => 0,
+        GuardLevel::Captain => 1,
+        GuardLevel::Admiral => 2,
+        GuardLevel::Governor => 3,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldValue<'a> {
+    Number(u64),
+    Money(Money),
+    Text(&'a str),
+    Guard(GuardLevel),
+}
+
+impl FieldValue<'_> {
+    fn compare(&self, literal: &Literal) -> Option<Ordering> {
+        match (self, literal) {
+            (FieldValue::Number(a), Literal::Number(b)) => Some(a.cmp(b)),
+            (FieldValue::Money(a), Literal::Money(b)) => Some(a.cmp(b)),
+            (FieldValue::Text(a), Literal::Text(b)) => Some((*a).cmp(b.as_str())),
+            (FieldValue::Guard(a), Literal::Guard(b)) => Some(guard_rank(*a).cmp(&guard_rank(*b))),
+            _ => None,
+        }
+    }
+}
+
+/// 条件中的值
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Literal {
+    Number(u64),
+    Money(Money),
+    Text(String),
+    Guard(GuardLevel),
+}
+
+impl From<u64> for Literal {
+    fn from(n: u64) -> Self {
+        Literal::Number(n)
+    }
+}
+
+impl From<Money> for Literal {
+    fn from(money: Money) -> Self {
+        Literal::Money(money)
+    }
+}
+
+impl From<&str> for Literal {
+    fn from(text: &str) -> Self {
+        Literal::Text(text.to_owned())
+    }
+}
+
+impl From<String> for Literal {
+    fn from(text: String) -> Self {
+        Literal::Text(text)
+    }
+}
+
+impl From<GuardLevel> for Literal {
+    fn from(level: GuardLevel) -> Self {
+        Literal::Guard(level)
+    }
+}
+
+fn is_bare(text: &str) -> bool {
+    text.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn write_text(f: &mut std::fmt::Formatter<'_>, text: &str) -> std::fmt::Result {
+    f.write_str("\"")?;
+    for c in text.chars() {
+        if c == '"' || c == '\\' {
+            f.write_str("\\")?;
+        }
+        f.write_fmt(format_args!("{}", c))?;
+    }
+    f.write_str("\"")
+}
+
+impl Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Literal::Number(n) => f.write_fmt(format_args!("{}", n)),
+            Literal::Money(money) if money.gold() % 1000 == 0 => {
+                f.write_fmt(format_args!("{}cny", money.gold() / 1000))
+            }
+            Literal::Money(money) => f.write_fmt(format_args!("{}gold", money.gold())),
+            Literal::Text(text) => write_text(f, text),
+            Literal::Guard(level) => f.write_str(match level {
+                GuardLevel::None => "none",
+                GuardLevel::Governor => "governor",
+                GuardLevel::Admiral => "admiral",
+                GuardLevel::Captain => "captain",
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl CmpOp {
+    fn test(self, ordering: Ordering) -> bool {
+        match self {
+            CmpOp::Eq => ordering.is_eq(),
+            CmpOp::Ne => ordering.is_ne(),
+            CmpOp::Gt => ordering.is_gt(),
+            CmpOp::Ge => ordering.is_ge(),
+            CmpOp::Lt => ordering.is_lt(),
+            CmpOp::Le => ordering.is_le(),
+        }
+    }
+}
+
+impl Display for CmpOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+        })
+    }
+}
+
+/// 事件过滤器，见[模块文档](self)
+///
+/// 在代码中构造时不检查字段和值的类型，类型不匹配的条件始终为假
+///
+///```no_run,ignore
+///let filter = EventFilter::kind(["danmaku", "gift"]) & Field::MedalLevel.ge(10);
+///```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum EventFilter {
+    /// 所有事件
+    #[default]
+    All,
+    Compare {
+        field: Field,
+        op: CmpOp,
+        value: Literal,
+    },
+    In {
+        field: Field,
+        values: Vec<Literal>,
+    },
+    /// 只对文本字段有效
+    Contains {
+        field: Field,
+        text: String,
+    },
+    Not(Box<EventFilter>),
+    And(Box<EventFilter>, Box<EventFilter>),
+    Or(Box<EventFilter>, Box<EventFilter>),
+}
+
+impl EventFilter {
+    pub fn compare(field: Field, op: CmpOp, value: impl Into<Literal>) -> Self {
+        EventFilter::Compare {
+            field,
+            op,
+            value: value.into(),
+        }
+    }
+
+    /// 事件类型为其中之一
+    pub fn kind<I, S>(kinds: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Field::Kind.one_of(kinds.into_iter().map(Into::into))
+    }
+
+    pub fn and(self, other: EventFilter) -> Self {
+        EventFilter::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: EventFilter) -> Self {
+        EventFilter::Or(Box::new(self), Box::new(other))
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        match self {
+            EventFilter::All => true,
+            EventFilter::Compare { field, op, value } => match field.get(event) {
+                Some(FieldValue::Text(name)) if *field == Field::Kind => match (op, value) {
+                    (CmpOp::Eq, Literal::Text(kind)) => kind_eq(name, kind),
+                    (CmpOp::Ne, Literal::Text(kind)) => !kind_eq(name, kind),
+                    _ => false,
+                },
+                Some(field_value) => field_value
+                    .compare(value)
+                    .is_some_and(|ordering| op.test(ordering)),
+                None => false,
+            },
+            EventFilter::In { field, values } => match field.get(event) {
+                Some(FieldValue::Text(name)) if *field == Field::Kind => values
+                    .iter()
+                    .any(|value| matches!(value, Literal::Text(kind) if kind_eq(name, kind))),
+                Some(field_value) => values
+                    .iter()
+                    .any(|value| field_value.compare(value).is_some_and(Ordering::is_eq)),
+                None => false,
+            },
+            EventFilter::Contains { field, text } => match field.get(event) {
+                Some(FieldValue::Text(value)) if *field != Field::Kind => value.contains(text),
+                _ => false,
+            },
+            EventFilter::Not(filter) => !filter.matches(event),
+            EventFilter::And(a, b) => a.matches(event) && b.matches(event),
+            EventFilter::Or(a, b) => a.matches(event) || b.matches(event),
+        }
+    }
+}
+
+impl std::ops::BitAnd for EventFilter {
+    type Output = EventFilter;
+
+    fn bitand(self, rhs: EventFilter) -> EventFilter {
+        self.and(rhs)
+    }
+}
+
+impl std::ops::BitOr for EventFilter {
+    type Output = EventFilter;
+
+    fn bitor(self, rhs: EventFilter) -> EventFilter {
+        self.or(rhs)
+    }
+}
+
+impl std::ops::Not for EventFilter {
+    type Output = EventFilter;
+
+    fn not(self) -> EventFilter {
+        EventFilter::Not(Box::new(self))
+    }
+}
+
+impl Display for EventFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let write_value =
+            |f: &mut std::fmt::Formatter<'_>, field: &Field, value: &Literal| match value {
+                Literal::Text(kind) if *field == Field::Kind && is_bare(kind) => f.write_str(kind),
+                value => f.write_fmt(format_args!("{}", value)),
+            };
+        match self {
+            EventFilter::All => f.write_str("true"),
+            EventFilter::Compare { field, op, value } => {
+                f.write_fmt(format_args!("{} {} ", field, op))?;
+                write_value(f, field, value)
+            }
+            EventFilter::In { field, values } => {
+                f.write_fmt(format_args!("{} in [", field))?;
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write_value(f, field, value)?;
+                }
+                f.write_str("]")
+            }
+            EventFilter::Contains { field, text } => {
+                f.write_fmt(format_args!("{} contains ", field))?;
+                write_text(f, text)
+            }
+            EventFilter::Not(filter) => f.write_fmt(format_args!("!({})", filter)),
+            EventFilter::And(a, b) => {
+                for (idx, filter) in [a, b].into_iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(" && ")?;
+                    }
+                    match filter.as_ref() {
+                        EventFilter::Or(..) => f.write_fmt(format_args!("({})", filter))?,
+                        filter => f.write_fmt(format_args!("{}", filter))?,
+                    }
+                }
+                Ok(())
+            }
+            EventFilter::Or(a, b) => f.write_fmt(format_args!("{} || {}", a, b)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterParseError {
+    /// 出错位置的字节偏移
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for FilterParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "过滤器语法错误（位置{}）：{}",
+            self.position, self.message
+        )
+    }
+}
+
+impl std::error::Error for FilterParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number { value: f64, unit: Option<String> },
+    Text(String),
+    Op(CmpOp),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+}
+
+fn tokenize(src: &str) -> Result<Vec<(usize, Token)>, FilterParseError> {
+    let err = |position: usize, message: &str| FilterParseError {
+        position,
+        message: message.to_owned(),
+    };
+    let mut tokens = vec![];
+    let mut chars = src.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+        let mut next_is = |expect: char| chars.next_if(|(_, c)| *c == expect).is_some();
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            ',' => Token::Comma,
+            '&' if next_is('&') => Token::And,
+            '|' if next_is('|') => Token::Or,
+            '=' if next_is('=') => Token::Op(CmpOp::Eq),
+            '!' if next_is('=') => Token::Op(CmpOp::Ne),
+            '!' => Token::Not,
+            '>' if next_is('=') => Token::Op(CmpOp::Ge),
+            '>' => Token::Op(CmpOp::Gt),
+            '<' if next_is('=') => Token::Op(CmpOp::Le),
+            '<' => Token::Op(CmpOp::Lt),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c)) => text.push(c),
+                            None => return Err(err(pos, "字符串没有结束")),
+                        },
+                        Some((_, c)) => text.push(c),
+                        None => return Err(err(pos, "字符串没有结束")),
+                    }
+                }
+                Token::Text(text)
+            }
+            c if c.is_ascii_digit() => {
+                let mut end = pos + 1;
+                while let Some((idx, _)) = chars.next_if(|(_, c)| c.is_ascii_digit() || *c == '.') {
+                    end = idx + 1;
+                }
+                let value = src[pos..end]
+                    .parse::<f64>()
+                    .map_err(|_| err(pos, "无效的数字"))?;
+                let unit_start = end;
+                while let Some((idx, _)) = chars.next_if(|(_, c)| c.is_ascii_alphabetic()) {
+                    end = idx + 1;
+                }
+                let unit = (end > unit_start).then(|| src[unit_start..end].to_ascii_lowercase());
+                Token::Number { value, unit }
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut end = pos + 1;
+                while let Some((idx, _)) =
+                    chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_' || *c == '.')
+                {
+                    end = idx + 1;
+                }
+                Token::Ident(src[pos..end].to_owned())
+            }
+            _ => return Err(err(pos, &format!("无法识别的字符`{}`", c))),
+        };
+        tokens.push((pos, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    cursor: usize,
+    len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.cursor).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.cursor)
+            .map(|(pos, _)| *pos)
+            .unwrap_or(self.len)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.cursor).map(|(_, token)| token.clone());
+        self.cursor += 1;
+        token
+    }
+
+    fn error(&self, message: impl Into<String>) -> FilterParseError {
+        FilterParseError {
+            position: self.position(),
+            message: message.into(),
+        }
+    }
+
+    fn expect(&mut self, expect: Token, message: &str) -> Result<(), FilterParseError> {
+        if self.peek() == Some(&expect) {
+            self.cursor += 1;
+            Ok(())
+        } else {
+            Err(self.error(message))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<EventFilter, FilterParseError> {
+        let mut filter = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.cursor += 1;
+            filter = filter.or(self.parse_and()?);
+        }
+        Ok(filter)
+    }
+
+    fn parse_and(&mut self) -> Result<EventFilter, FilterParseError> {
+        let mut filter = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.cursor += 1;
+            filter = filter.and(self.parse_unary()?);
+        }
+        Ok(filter)
+    }
+
+    fn parse_unary(&mut self) -> Result<EventFilter, FilterParseError> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.cursor += 1;
+                Ok(!self.parse_unary()?)
+            }
+            Some(Token::LParen) => {
+                self.cursor += 1;
+                let filter = self.parse_or()?;
+                self.expect(Token::RParen, "缺少`)`")?;
+                Ok(filter)
+            }
+            _ => self.parse_condition(),
+        }
+    }
+
+    fn parse_condition(&mut self) -> Result<EventFilter, FilterParseError> {
+        let position = self.position();
+        let name = match self.next() {
+            Some(Token::Ident(name)) => name,
+            _ => {
+                return Err(FilterParseError {
+                    position,
+                    message: String::from("需要字段或者条件"),
+                })
+            }
+        };
+        match name.as_str() {
+            "true" => return Ok(EventFilter::All),
+            "false" => return Ok(!EventFilter::All),
+            _ => {}
+        }
+        let field = Field::from_name(&name).ok_or_else(|| FilterParseError {
+            position,
+            message: format!("未知的字段`{}`", name),
+        })?;
+        match self.next() {
+            Some(Token::Op(op)) => {
+                let value = self.parse_literal(field)?;
+                if field.ty() == FieldType::Kind && !matches!(op, CmpOp::Eq | CmpOp::Ne) {
+                    return Err(FilterParseError {
+                        position,
+                        message: String::from("`kind`只能使用`==`、`!=`和`in`"),
+                    });
+                }
+                Ok(EventFilter::Compare { field, op, value })
+            }
+            Some(Token::Ident(keyword)) if keyword == "in" => {
+                self.expect(Token::LBracket, "`in`之后需要`[`")?;
+                let mut values = vec![];
+                if self.peek() != Some(&Token::RBracket) {
+                    values.push(self.parse_literal(field)?);
+                    while self.peek() == Some(&Token::Comma) {
+                        self.cursor += 1;
+                        values.push(self.parse_literal(field)?);
+                    }
+                }
+                self.expect(Token::RBracket, "缺少`]`")?;
+                Ok(EventFilter::In { field, values })
+            }
+            Some(Token::Ident(keyword)) if keyword == "contains" => {
+                if field.ty() != FieldType::Text {
+                    return Err(FilterParseError {
+                        position,
+                        message: format!("`{}`不是文本字段", field),
+                    });
+                }
+                match self.parse_literal(field)? {
+                    Literal::Text(text) => Ok(EventFilter::Contains { field, text }),
+                    _ => Err(self.error("需要字符串")),
+                }
+            }
+            _ => {
+                self.cursor -= 1;
+                Err(self.error(format!("`{}`之后需要运算符", field)))
+            }
+        }
+    }
+
+    fn parse_literal(&mut self, field: Field) -> Result<Literal, FilterParseError> {
+        let position = self.position();
+        let literal = match self.next() {
+            Some(Token::Number { value, unit: None }) if value.fract() == 0.0 => {
+                Literal::Number(value as u64)
+            }
+            Some(Token::Number {
+                value,
+                unit: Some(unit),
+            }) => {
+                let gold = match unit.as_str() {
+                    "cny" => value * 1000.0,
+                    "battery" => value * 100.0,
+                    "gold" => value,
+                    unit => {
+                        return Err(FilterParseError {
+                            position,
+                            message: format!(
+                                "未知的金额单位`{}`，可以使用cny、battery和gold",
+                                unit
+                            ),
+                        })
+                    }
+                };
+                Literal::Money(Money::from_gold(gold.round() as u64))
+            }
+            Some(Token::Text(text)) => Literal::Text(text),
+            Some(Token::Ident(ident)) if field.ty() == FieldType::Kind => Literal::Text(ident),
+            Some(Token::Ident(ident)) if field.ty() == FieldType::Guard => {
+                Literal::Guard(match ident.as_str() {
+                    "none" => GuardLevel::None,
+                    "governor" => GuardLevel::Governor,
+                    "admiral" => GuardLevel::Admiral,
+                    "captain" => GuardLevel::Captain,
+                    _ => {
+                        return Err(FilterParseError {
+                            position,
+                            message: format!("未知的大航海等级`{}`", ident),
+                        })
+                    }
+                })
+            }
+            _ => {
+                return Err(FilterParseError {
+                    position,
+                    message: String::from("需要值"),
+                })
+            }
+        };
+        if let Literal::Text(kind) = &literal {
+            if field.ty() == FieldType::Kind
+                && !EventData::NAMES.iter().any(|name| kind_eq(name, kind))
+            {
+                return Err(FilterParseError {
+                    position,
+                    message: format!("未知的事件类型`{}`", kind),
+                });
+            }
+        }
+        field.accept(literal).ok_or_else(|| FilterParseError {
+            position,
+            message: format!("`{}`的值类型不匹配", field),
+        })
+    }
+}
+
+impl FromStr for EventFilter {
+    type Err = FilterParseError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(src)?,
+            cursor: 0,
+            len: src.len(),
+        };
+        if parser.peek().is_none() {
+            return Ok(EventFilter::All);
+        }
+        let filter = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(_) => Err(parser.error("多余的内容")),
+        }
+    }
+}
+
+impl Serialize for EventFilter {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for EventFilter {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let src = String::deserialize(deserializer)?;
+        src.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// 见[`FilterExt::filter_events`]
+pub struct Filtered<S> {
+    inner: S,
+    filter: EventFilter,
+}
+
+impl<S> Filtered<S> {
+    pub fn filter(&self) -> &EventFilter {
+        &self.filter
+    }
+
+    /// 替换过滤条件，之后的事件按新的条件过滤
+    pub fn set_filter(&mut self, filter: EventFilter) {
+        self.filter = filter;
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S> Stream for Filtered<S>
+where
+    S: Stream<Item = Event> + Unpin,
+{
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(event)) if this.filter.matches(&event) => {
+                    return Poll::Ready(Some(event))
+                }
+                Poll::Ready(Some(_)) => continue,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+pub trait FilterExt: Stream<Item = Event> + Sized {
+    /// 只保留满足`filter`的事件
+    fn filter_events(self, filter: EventFilter) -> Filtered<Self> {
+        Filtered {
+            inner: self,
+            filter,
+        }
+    }
+}
+
+impl<S: Stream<Item = Event>> FilterExt for S {}
@@ -20,6 +20,8 @@
 #![deny(clippy::unwrap_used, clippy::print_stdout, clippy::panic)]
 // #![feature(split_array)]
 #[cfg(feature = "connect")]
+pub mod api;
+#[cfg(feature = "connect")]
 pub mod connection;
 #[cfg(feature = "connect")]
 mod connector;
@@ -29,11 +31,31 @@ pub use crate::connector::*;
 pub use connection::Connection;
 #[cfg(feature = "connect")]
 pub(crate) mod cmd;
+#[cfg(feature = "rt_tokio")]
+pub mod enrich;
+#[cfg(feature = "connect")]
+pub mod moderation;
+#[cfg(feature = "open_platform")]
+pub mod open_platform;
+#[cfg(feature = "rt_tokio")]
+pub mod record;
+#[cfg(feature = "rt_tokio")]
+mod room_manager;
+#[cfg(feature = "rt_tokio")]
+pub mod router;
+#[cfg(feature = "connect")]
+pub mod send;
+#[cfg(feature = "rt_tokio")]
+pub use room_manager::*;
 
 #[cfg(feature = "event")]
 pub mod event;
 #[cfg(feature = "event")]
+pub mod filter;
+#[cfg(feature = "event")]
 pub mod model;
+#[cfg(feature = "event")]
+pub mod pk;
 
 #[cfg(test)]
 mod tests;
@@ -44,3 +66,5 @@ mod error;
 mod packet;
 #[cfg(feature = "connect")]
 pub use error::Error;
+#[cfg(feature = "connect")]
+pub use packet::{Auth, Operation, RawPacket};
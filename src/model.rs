@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Debug, Clone, Deserialize, Serialize, Hash)]
 pub struct Emoticon {
@@ -9,15 +9,97 @@ pub struct Emoticon {
     pub width: u64,
     pub url: String,
 }
+/// 大航海等级，序列化为数字，1，2，3分别为总督，提督，舰长；0为无
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum GuardLevel {
+    #[default]
+    None,
+    /// 总督
+    Governor,
+    /// 提督
+    Admiral,
+    /// 舰长
+    Captain,
+}
+
+impl GuardLevel {
+    pub fn as_u64(self) -> u64 {
+        match self {
+            GuardLevel::None => 0,
+            GuardLevel::Governor => 1,
+            GuardLevel::Admiral => 2,
+            GuardLevel::Captain => 3,
+        }
+    }
+
+    pub fn is_guard(self) -> bool {
+        self != GuardLevel::None
+    }
+}
+
+impl From<u64> for GuardLevel {
+    fn from(level: u64) -> Self {
+        match level {
+            1 => GuardLevel::Governor,
+            2 => GuardLevel::Admiral,
+            3 => GuardLevel::Captain,
+            _ => GuardLevel::None,
+        }
+    }
+}
+
+impl Serialize for GuardLevel {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.as_u64())
+    }
+}
+
+impl<'de> Deserialize<'de> for GuardLevel {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u64::deserialize(deserializer).map(GuardLevel::from)
+    }
+}
+
+impl Display for GuardLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            GuardLevel::None => "无",
+            GuardLevel::Governor => "总督",
+            GuardLevel::Admiral => "提督",
+            GuardLevel::Captain => "舰长",
+        })
+    }
+}
+
+/// 弹幕的标记位，序列化为数字
+/// - 第一位：是否是抽奖弹幕
+/// - 2~4位：大航海等级
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct DanmakuFlags(pub u64);
+
+impl DanmakuFlags {
+    pub fn new(is_lottery: bool, guard: GuardLevel) -> Self {
+        DanmakuFlags(is_lottery as u64 | (guard.as_u64() << 1))
+    }
+
+    pub fn is_lottery(self) -> bool {
+        self.0 & 1 != 0
+    }
+
+    pub fn guard(self) -> GuardLevel {
+        GuardLevel::from((self.0 >> 1) & 0b111)
+    }
+}
+
 ///
 /// # 说明
-/// - `guard_level`字段，1，2，3分别为总督，提督，舰长；0为无。
 /// - `anchor_roomid` 大航海房间id
 #[derive(Debug, Clone, Deserialize, Serialize, Hash)]
 pub struct FansMedal {
     pub anchor_roomid: u64,
     #[serde(default)]
-    pub guard_level: u64,
+    pub guard_level: GuardLevel,
     pub medal_level: u64,
     pub medal_name: String,
 }
@@ -27,6 +109,12 @@ pub struct User {
     pub uid: u64,
     pub uname: String,
     pub face: Option<String>,
+    /// 用户等级，弹幕协议中没有这个字段，可以通过`enrich`模块补全
+    #[serde(default)]
+    pub level: Option<u64>,
+    /// 开放平台中用户的唯一标识，开放平台不再下发`uid`，此时`uid`为0
+    #[serde(default)]
+    pub open_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Hash)]
@@ -42,15 +130,267 @@ pub enum CoinType {
     Gold,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, Hash)]
+/// 金额，以金瓜子为单位保存，1000金瓜子为1元，100金瓜子为1电池
+///
+/// 不同事件中的价格单位不同，统一转换为[`Money`]后可以直接相加，运算溢出时取上限
+///
+/// 序列化为`{"gold": 金瓜子数量}`，带上单位以免和旧版本中的价格混淆；
+/// 反序列化时兼容旧版本的数字，按金瓜子处理，旧版本的[`crate::event::SuperChatEvent::price`]按元处理
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money(u64);
+
+#[derive(Serialize, Deserialize)]
+struct TaggedMoney {
+    gold: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CompatMoney {
+    Tagged(TaggedMoney),
+    Bare(u64),
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        TaggedMoney { gold: self.0 }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Money::deserialize_compat(deserializer, Money::from_gold)
+    }
+}
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub const fn from_gold(gold: u64) -> Self {
+        Money(gold)
+    }
+
+    pub const fn from_cny(cny: u64) -> Self {
+        Money(cny.saturating_mul(1000))
+    }
+
+    pub const fn from_battery(battery: u64) -> Self {
+        Money(battery.saturating_mul(100))
+    }
+
+    /// 溢出时返回`None`
+    pub const fn checked_add(self, rhs: Money) -> Option<Money> {
+        match self.0.checked_add(rhs.0) {
+            Some(gold) => Some(Money(gold)),
+            None => None,
+        }
+    }
+
+    /// 溢出时返回`None`
+    pub const fn checked_mul(self, rhs: u64) -> Option<Money> {
+        match self.0.checked_mul(rhs) {
+            Some(gold) => Some(Money(gold)),
+            None => None,
+        }
+    }
+
+    /// 旧版本中超级留言的价格是以元为单位的数字
+    pub(crate) fn deserialize_cny_compat<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Money, D::Error> {
+        Money::deserialize_compat(deserializer, Money::from_cny)
+    }
+
+    /// 非自描述的格式（比如bincode）只支持新的格式
+    fn deserialize_compat<'de, D: Deserializer<'de>>(
+        deserializer: D,
+        bare: fn(u64) -> Money,
+    ) -> Result<Money, D::Error> {
+        if !deserializer.is_human_readable() {
+            return TaggedMoney::deserialize(deserializer).map(|money| Money(money.gold));
+        }
+        Ok(match CompatMoney::deserialize(deserializer)? {
+            CompatMoney::Tagged(money) => Money(money.gold),
+            CompatMoney::Bare(count) => bare(count),
+        })
+    }
+
+    /// 金瓜子数量
+    pub const fn gold(self) -> u64 {
+        self.0
+    }
+
+    pub fn cny(self) -> f64 {
+        self.0 as f64 / 1000.0
+    }
+
+    pub fn battery(self) -> f64 {
+        self.0 as f64 / 100.0
+    }
+}
+
+impl std::ops::Add for Money {
+    type Output = Money;
+
+    fn add(self, rhs: Money) -> Money {
+        Money(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl std::ops::AddAssign for Money {
+    fn add_assign(&mut self, rhs: Money) {
+        *self = *self + rhs
+    }
+}
+
+impl std::ops::Mul<u64> for Money {
+    type Output = Money;
+
+    fn mul(self, rhs: u64) -> Money {
+        Money(self.0.saturating_mul(rhs))
+    }
+}
+
+impl std::iter::Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::ZERO, |sum, money| sum + money)
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:.2}CNY", self.cny()))
+    }
+}
+
+/// 礼物的价格，银瓜子没有现金价值
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Coin {
+    Gold(Money),
+    /// 银瓜子数量
+    Silver(u64),
+}
+
+impl Coin {
+    pub fn new(coin_type: CoinType, count: u64) -> Self {
+        match coin_type {
+            CoinType::Gold => Coin::Gold(Money::from_gold(count)),
+            CoinType::Silver => Coin::Silver(count),
+        }
+    }
+
+    pub fn coin_type(&self) -> CoinType {
+        match self {
+            Coin::Gold(_) => CoinType::Gold,
+            Coin::Silver(_) => CoinType::Silver,
+        }
+    }
+
+    /// 瓜子数量
+    pub fn count(&self) -> u64 {
+        match self {
+            Coin::Gold(money) => money.gold(),
+            Coin::Silver(count) => *count,
+        }
+    }
+
+    /// 现金价值，银瓜子为0
+    pub fn value(&self) -> Money {
+        match self {
+            Coin::Gold(money) => *money,
+            Coin::Silver(_) => Money::ZERO,
+        }
+    }
+}
+
+/// - `price` 为单价
+/// - `total` 为总价，即`price`乘以`num`
+///
+/// 兼容旧版本的`coin_type`、`coin_count`和不带单位的`price`字段
+#[derive(Clone, Debug, Serialize, Hash)]
 pub struct Gift {
-    pub coin_type: CoinType,
-    pub coin_count: u64,
     pub action: String,
     pub gift_name: String,
     pub gift_id: u64,
     pub num: u64,
-    pub price: u64,
+    pub price: Coin,
+    pub total: Coin,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Gift")]
+struct CurrentGift {
+    action: String,
+    gift_name: String,
+    gift_id: u64,
+    num: u64,
+    price: Coin,
+    total: Coin,
+}
+
+/// 旧版本的礼物，`coin_count`为总价，`price`为单价
+#[derive(Deserialize)]
+struct LegacyGift {
+    coin_type: CoinType,
+    coin_count: u64,
+    action: String,
+    gift_name: String,
+    gift_id: u64,
+    num: u64,
+    price: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CompatGift {
+    Current(CurrentGift),
+    Legacy(LegacyGift),
+}
+
+impl From<CurrentGift> for Gift {
+    fn from(gift: CurrentGift) -> Self {
+        Gift {
+            action: gift.action,
+            gift_name: gift.gift_name,
+            gift_id: gift.gift_id,
+            num: gift.num,
+            price: gift.price,
+            total: gift.total,
+        }
+    }
+}
+
+impl From<LegacyGift> for Gift {
+    fn from(gift: LegacyGift) -> Self {
+        Gift {
+            action: gift.action,
+            gift_name: gift.gift_name,
+            gift_id: gift.gift_id,
+            num: gift.num,
+            price: Coin::new(gift.coin_type, gift.price),
+            total: Coin::new(gift.coin_type, gift.coin_count),
+        }
+    }
+}
+
+/// 与[`Money`]相同，只有可读的格式兼容旧版本
+impl<'de> Deserialize<'de> for Gift {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return CurrentGift::deserialize(deserializer).map(Gift::from);
+        }
+        Ok(match CompatGift::deserialize(deserializer)? {
+            CompatGift::Current(gift) => gift.into(),
+            CompatGift::Legacy(gift) => gift.into(),
+        })
+    }
+}
+
+impl Gift {
+    pub fn value(&self) -> Money {
+        self.total.value()
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, Hash)]
@@ -60,6 +400,158 @@ pub struct GiftType {
     pub gift_id: u64,
 }
 
+/// 关注的类型，对应`INTERACT_WORD`的`msg_type`字段
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum FollowKind {
+    /// msg_type 2
+    Follow,
+    /// msg_type 4
+    SpecialFollow,
+    /// msg_type 5
+    MutualFollow,
+}
+
+/// 通知的来源
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum NoticeKind {
+    /// `NOTICE_MSG`，全站或分区广播，比如其他直播间的大额礼物
+    Broadcast,
+    /// `COMMON_NOTICE_DANMAKU`，直播间内的系统提示
+    Common,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash, Default)]
+pub enum LiveStatus {
+    #[default]
+    Offline,
+    Live,
+    /// 轮播中
+    Round,
+}
+
+impl From<u64> for LiveStatus {
+    fn from(status: u64) -> Self {
+        match status {
+            1 => LiveStatus::Live,
+            2 => LiveStatus::Round,
+            _ => LiveStatus::Offline,
+        }
+    }
+}
+
+/// 直播间和主播的信息
+/// - `live_start_time` 为开播时间的时间戳，未开播为`None`
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash, Default)]
+pub struct RoomMetadata {
+    pub room_id: u64,
+    pub short_id: u64,
+    pub uid: u64,
+    pub uname: String,
+    pub face: String,
+    pub title: String,
+    pub area_id: u64,
+    pub area_name: String,
+    pub parent_area_id: u64,
+    pub parent_area_name: String,
+    pub live_status: LiveStatus,
+    pub live_start_time: Option<u64>,
+}
+
+impl RoomMetadata {
+    /// 根据房间信息变更和开播、下播事件更新，返回是否发生变化
+    ///
+    /// 来源不是这个直播间的事件会被忽略
+    pub fn update(&mut self, event: &crate::event::Event) -> bool {
+        use crate::event::*;
+        if let Some(source) = &event.meta.source {
+            if source.room_id != self.room_id {
+                return false;
+            }
+        }
+        match &event.data {
+            EventData::RoomChange(evt) => {
+                self.title.clone_from(&evt.title);
+                self.area_id = evt.area_id as u64;
+                self.area_name.clone_from(&evt.area_name);
+                self.parent_area_id = evt.parent_area_id as u64;
+                self.parent_area_name.clone_from(&evt.parent_area_name);
+                true
+            }
+            EventData::LiveStartEvent(evt) if evt.room_id == self.room_id => {
+                self.live_status = LiveStatus::Live;
+                self.live_start_time = (evt.live_time != 0).then_some(evt.live_time);
+                true
+            }
+            EventData::LiveStopEvent(evt) if evt.room_id == self.room_id => self.stop(),
+            EventData::StopLiveEvent(evt) if evt.room_id_list.contains(&self.room_id) => {
+                self.stop()
+            }
+            _ => false,
+        }
+    }
+
+    fn stop(&mut self) -> bool {
+        let changed = self.live_status == LiveStatus::Live;
+        self.live_status = LiveStatus::Offline;
+        self.live_start_time = None;
+        changed
+    }
+}
+
+/// 房间禁言的范围，对应`ROOM_SILENT_ON`的`type`字段
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum SilenceKind {
+    /// 用户等级低于`level`的用户
+    Level,
+    /// 粉丝勋章等级低于`level`的用户
+    Medal,
+    /// 全员
+    Member,
+}
+
+/// 天选时刻的礼物参与条件
+#[derive(Clone, Debug, Deserialize, Serialize, Hash)]
+pub struct LotteryGiftRequirement {
+    pub gift_id: u64,
+    pub gift_name: String,
+    pub gift_num: u64,
+    pub gift_price: Money,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Hash)]
+pub struct RedPocketAward {
+    pub gift_id: u64,
+    pub gift_name: String,
+    pub gift_pic: String,
+    pub num: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Hash)]
+pub struct RedPocketWinner {
+    pub user: User,
+    /// 中奖的礼物，列表中找不到对应的礼物时为`None`
+    pub award: Option<RedPocketWinnerAward>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Hash)]
+pub struct RedPocketWinnerAward {
+    pub award_id: u64,
+    pub award_name: String,
+    pub award_price: Money,
+}
+
+/// PK中一方的状态
+/// - `best_uname` 该方助攻最多的用户
+#[derive(Clone, Debug, Deserialize, Serialize, Hash)]
+pub struct PkSide {
+    pub room_id: u64,
+    #[serde(default)]
+    pub votes: u64,
+    #[serde(default)]
+    pub best_uname: String,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, Hash)]
 #[serde(tag = "tag", content = "data")]
 pub enum DanmakuMessage {
@@ -92,12 +584,15 @@ impl Display for DanmakuMessage {
 
 impl Display for Gift {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!(
-            "{}{}x{}[{:.2}CNY]",
-            self.action,
-            self.gift_name,
-            self.num,
-            ((self.price * self.num) as f32) / 1000.0
-        ))
+        match self.total {
+            Coin::Gold(money) => f.write_fmt(format_args!(
+                "{}{}x{}[{}]",
+                self.action, self.gift_name, self.num, money
+            )),
+            Coin::Silver(count) => f.write_fmt(format_args!(
+                "{}{}x{}[{}银瓜子]",
+                self.action, self.gift_name, self.num, count
+            )),
+        }
     }
 }
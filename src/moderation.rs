@@ -0,0 +1,252 @@
+//! 房管操作
+//!
+//! 需要连接器登录的账号是主播或者房管，csrf取自登录信息，见[`Connector::csrf`]。
+//! 操作成功后，服务器会在事件流中推送对应的[`crate::event::UserBlockedEvent`]、
+//! [`crate::event::RoomSilentOnEvent`]和[`crate::event::RoomSilentOffEvent`]
+//!
+//!```no_run,ignore
+//!while let Some(Ok(evt)) = stream.next().await {
+//!    if let EventData::DanmakuEvent(danmaku) = evt.data {
+//!        if danmaku.message.to_string().contains("广告") {
+//!            connector.mute_user(danmaku.user.uid, MuteDuration::Hours(1)).await?;
+//!        }
+//!    }
+//!}
+//!```
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    api::{ApiError, ApiResponse},
+    model::SilenceKind,
+    Connector,
+};
+
+const ADD_SILENT_USER_URL: &str =
+    "https://api.live.bilibili.com/xlive/web-ucenter/v1/banned/AddSilentUser";
+const DEL_SILENT_USER_URL: &str =
+    "https://api.live.bilibili.com/xlive/web-ucenter/v1/banned/DelSilentUser";
+const SILENT_USER_LIST_URL: &str =
+    "https://api.live.bilibili.com/xlive/web-ucenter/v1/banned/GetSilentUserList";
+const ROOM_SILENT_URL: &str = "https://api.live.bilibili.com/xlive/web-room/v1/banned/RoomSilent";
+const ADD_SHIELD_KEYWORD_URL: &str =
+    "https://api.live.bilibili.com/xlive/web-ucenter/v1/banned/AddShieldKeyword";
+const DEL_SHIELD_KEYWORD_URL: &str =
+    "https://api.live.bilibili.com/xlive/web-ucenter/v1/banned/DelShieldKeyword";
+
+#[derive(Debug)]
+pub enum ModerationError {
+    /// 登录信息中没有`bili_jct`，见[`Connector::csrf`]
+    MissingCsrf,
+    /// -101，未登录
+    NotLoggedIn,
+    /// -400，请求参数错误
+    BadRequest {
+        message: String,
+    },
+    Api {
+        code: i64,
+        message: String,
+    },
+    Http(reqwest::Error),
+    Deser(serde_json::Error),
+}
+
+impl std::fmt::Display for ModerationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ModerationError::*;
+        match self {
+            MissingCsrf => write!(f, "缺少csrf"),
+            NotLoggedIn => write!(f, "未登录"),
+            BadRequest { message } => write!(f, "请求错误：{}", message),
+            Api { code, message } => write!(f, "操作失败[{}]：{}", code, message),
+            Http(e) => write!(f, "http错误：{}", e),
+            Deser(e) => write!(f, "解析响应失败：{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ModerationError {}
+
+impl From<ApiError> for ModerationError {
+    fn from(e: ApiError) -> Self {
+        match e {
+            ApiError::NotLoggedIn => ModerationError::NotLoggedIn,
+            ApiError::BadRequest { message } => ModerationError::BadRequest { message },
+            ApiError::Api { code, message } => ModerationError::Api { code, message },
+        }
+    }
+}
+
+impl From<serde_json::Error> for ModerationError {
+    fn from(e: serde_json::Error) -> Self {
+        ModerationError::Deser(e)
+    }
+}
+
+/// 禁言时长
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MuteDuration {
+    /// 到本场直播结束
+    ThisLive,
+    Hours(u32),
+    Forever,
+}
+
+impl MuteDuration {
+    /// 接口中的`hour`参数
+    pub(crate) fn hour(self) -> i64 {
+        match self {
+            MuteDuration::ThisLive => 0,
+            MuteDuration::Hours(hours) => hours as i64,
+            MuteDuration::Forever => -1,
+        }
+    }
+}
+
+/// 房间禁言设置
+/// - `minutes` 为禁言时长，0为到本场直播结束
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RoomSilence {
+    On {
+        kind: SilenceKind,
+        level: u64,
+        minutes: u64,
+    },
+    Off,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BannedUser {
+    #[serde(rename = "tuid")]
+    pub uid: u64,
+    #[serde(rename = "tname")]
+    pub uname: String,
+    #[serde(default)]
+    pub face: Option<String>,
+    /// 操作者的用户名
+    #[serde(rename = "name", default)]
+    pub operator_name: String,
+    #[serde(default)]
+    pub ctime: String,
+    /// 解除禁言的时间，永久禁言时为空
+    #[serde(default)]
+    pub block_end_time: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BannedUserPage {
+    #[serde(rename = "data", default)]
+    pub users: Vec<BannedUser>,
+    #[serde(default)]
+    pub total: u64,
+    #[serde(default)]
+    pub total_page: u64,
+}
+
+impl RoomSilence {
+    /// 接口中的`type`、`level`和`minute`参数
+    pub(crate) fn form(self) -> Vec<(&'static str, String)> {
+        match self {
+            RoomSilence::On {
+                kind,
+                level,
+                minutes,
+            } => {
+                let kind = match kind {
+                    SilenceKind::Level => "level",
+                    SilenceKind::Medal => "medal",
+                    SilenceKind::Member => "member",
+                };
+                vec![
+                    ("type", String::from(kind)),
+                    ("level", level.to_string()),
+                    ("minute", minutes.to_string()),
+                ]
+            }
+            RoomSilence::Off => vec![
+                ("type", String::from("off")),
+                ("level", String::from("0")),
+                ("minute", String::from("0")),
+            ],
+        }
+    }
+}
+
+impl Connector {
+    /// 补全所有房管操作都需要的房间号和csrf
+    pub(crate) fn moderation_form(
+        &self,
+        mut form: Vec<(&'static str, String)>,
+    ) -> Result<Vec<(&'static str, String)>, ModerationError> {
+        let csrf = self.csrf().ok_or(ModerationError::MissingCsrf)?.to_owned();
+        form.push(("room_id", self.roomid.to_string()));
+        form.push(("csrf", csrf.clone()));
+        form.push(("csrf_token", csrf));
+        Ok(form)
+    }
+
+    async fn moderate<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        form: Vec<(&'static str, String)>,
+    ) -> Result<T, ModerationError> {
+        let form = self.moderation_form(form)?;
+        self.client()
+            .inner()
+            .post(url)
+            .form(&form)
+            .send()
+            .await
+            .map_err(ModerationError::Http)?
+            .json::<ApiResponse>()
+            .await
+            .map_err(ModerationError::Http)?
+            .into_data()
+    }
+
+    pub async fn mute_user(&self, uid: u64, duration: MuteDuration) -> Result<(), ModerationError> {
+        let form = vec![
+            ("tuid", uid.to_string()),
+            ("hour", duration.hour().to_string()),
+            ("mobile_app", String::from("web")),
+        ];
+        self.moderate::<Value>(ADD_SILENT_USER_URL, form)
+            .await
+            .map(drop)
+    }
+
+    pub async fn unmute_user(&self, uid: u64) -> Result<(), ModerationError> {
+        let form = vec![("tuid", uid.to_string())];
+        self.moderate::<Value>(DEL_SILENT_USER_URL, form)
+            .await
+            .map(drop)
+    }
+
+    /// `page`从1开始
+    pub async fn banned_users(&self, page: u64) -> Result<BannedUserPage, ModerationError> {
+        let form = vec![("ps", page.to_string())];
+        self.moderate(SILENT_USER_LIST_URL, form).await
+    }
+
+    pub async fn set_room_silence(&self, silence: RoomSilence) -> Result<(), ModerationError> {
+        self.moderate::<Value>(ROOM_SILENT_URL, silence.form())
+            .await
+            .map(drop)
+    }
+
+    /// 添加屏蔽词，包含屏蔽词的弹幕不会被其他观众看到
+    pub async fn add_blocked_keyword(&self, keyword: &str) -> Result<(), ModerationError> {
+        let form = vec![("keyword", keyword.to_owned())];
+        self.moderate::<Value>(ADD_SHIELD_KEYWORD_URL, form)
+            .await
+            .map(drop)
+    }
+
+    pub async fn remove_blocked_keyword(&self, keyword: &str) -> Result<(), ModerationError> {
+        let form = vec![("keyword", keyword.to_owned())];
+        self.moderate::<Value>(DEL_SHIELD_KEYWORD_URL, form)
+            .await
+            .map(drop)
+    }
+}
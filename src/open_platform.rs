@@ -0,0 +1,588 @@
+//! 直播开放平台（互动玩法）
+//!
+//! 开放平台的http接口需要用`access_key_id`和`access_key_secret`签名，开始玩法后得到专用的wss地址和鉴权包体，
+//! 之后的连接和网页端协议相同，`LIVE_OPEN_PLATFORM_*`指令会转换成对应的[`crate::event::EventData`]
+//!
+//! 开放平台不再下发真实的uid，转换后的[`crate::model::User::uid`]可能为0，
+//! 用[`crate::model::User::open_id`]区分用户
+//!
+//!```no_run,ignore
+//!let client = OpenPlatformClient::new(access_key_id, access_key_secret, app_id);
+//!let session = client.start(code).await?;
+//!let _heartbeat = client.spawn_heartbeat(session.game_id.clone());
+//!let mut stream = session.connect().await?;
+//!while let Some(evt) = stream.next().await {
+//!    log::info!("{:?}", evt);
+//!}
+//!client.end(&session.game_id).await?;
+//!```
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use hmac::{Hmac, Mac};
+use md5::{Digest, Md5};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+
+use crate::{
+    api::{ApiError, ApiResponse},
+    event::*,
+    model::*,
+};
+
+pub const OPEN_PLATFORM_HOST: &str = "https://live-open.biliapi.com";
+
+/// 开放平台要求每20秒调用一次[`OpenPlatformClient::heartbeat`]，超时后玩法会被关闭
+pub const GAME_HEARTBEAT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(20);
+
+#[derive(Debug)]
+pub enum OpenPlatformError {
+    Http(reqwest::Error),
+    /// 接口返回的code不为0
+    Api {
+        code: i64,
+        message: String,
+    },
+    Deser(serde_json::Error),
+}
+
+impl std::fmt::Display for OpenPlatformError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpenPlatformError::Http(e) => write!(f, "http错误：{}", e),
+            OpenPlatformError::Api { code, message } => {
+                write!(f, "开放平台错误[{}]：{}", code, message)
+            }
+            OpenPlatformError::Deser(e) => write!(f, "解析响应失败：{}", e),
+        }
+    }
+}
+
+impl std::error::Error for OpenPlatformError {}
+
+/// 开放平台的错误都保留原始的code
+impl From<ApiError> for OpenPlatformError {
+    fn from(e: ApiError) -> Self {
+        let (code, message) = match e {
+            ApiError::NotLoggedIn => (-101, String::from("未登录")),
+            ApiError::BadRequest { message } => (-400, message),
+            ApiError::Api { code, message } => (code, message),
+        };
+        OpenPlatformError::Api { code, message }
+    }
+}
+
+impl From<serde_json::Error> for OpenPlatformError {
+    fn from(e: serde_json::Error) -> Self {
+        OpenPlatformError::Deser(e)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnchorInfo {
+    pub room_id: u64,
+    pub uname: String,
+    pub uface: String,
+    #[serde(default)]
+    pub uid: u64,
+    #[serde(default)]
+    pub open_id: String,
+}
+
+/// [`OpenPlatformClient::start`]返回的玩法场次
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameSession {
+    pub game_id: String,
+    /// 鉴权包的包体，原样发送
+    pub auth_body: String,
+    pub wss_link: Vec<String>,
+    pub anchor_info: AnchorInfo,
+}
+
+#[derive(Deserialize)]
+struct StartData {
+    game_info: GameInfo,
+    websocket_info: WebsocketInfo,
+    anchor_info: AnchorInfo,
+}
+
+#[derive(Deserialize)]
+struct GameInfo {
+    game_id: String,
+}
+
+#[derive(Deserialize)]
+struct WebsocketInfo {
+    auth_body: String,
+    wss_link: Vec<String>,
+}
+
+/// 生成签名后的请求头，`body`为请求体的原始字节
+///
+/// 所有`x-bili-`开头的头按字典序以`key:value`拼接，用`\n`分隔，
+/// 对其用`access_key_secret`计算HMAC-SHA256作为`Authorization`
+pub fn sign_headers(
+    access_key_id: &str,
+    access_key_secret: &str,
+    body: &[u8],
+    timestamp: i64,
+    nonce: &str,
+) -> BTreeMap<&'static str, String> {
+    let mut headers = BTreeMap::new();
+    headers.insert("x-bili-accesskeyid", access_key_id.to_owned());
+    headers.insert("x-bili-content-md5", hex::encode(Md5::digest(body)));
+    headers.insert("x-bili-signature-method", String::from("HMAC-SHA256"));
+    headers.insert("x-bili-signature-nonce", nonce.to_owned());
+    headers.insert("x-bili-signature-version", String::from("1.0"));
+    headers.insert("x-bili-timestamp", timestamp.to_string());
+    let sign_str = headers
+        .iter()
+        .map(|(k, v)| format!("{k}:{v}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut mac = Hmac::<Sha256>::new_from_slice(access_key_secret.as_bytes())
+        .expect("hmac accepts keys of any size");
+    mac.update(sign_str.as_bytes());
+    headers.insert("Authorization", hex::encode(mac.finalize().into_bytes()));
+    headers
+}
+
+/// 开放平台的http客户端，可以克隆
+#[derive(Debug, Clone)]
+pub struct OpenPlatformClient {
+    access_key_id: String,
+    access_key_secret: String,
+    app_id: i64,
+    host: String,
+    http: reqwest::Client,
+}
+
+impl OpenPlatformClient {
+    pub fn new(
+        access_key_id: impl Into<String>,
+        access_key_secret: impl Into<String>,
+        app_id: i64,
+    ) -> Self {
+        OpenPlatformClient {
+            access_key_id: access_key_id.into(),
+            access_key_secret: access_key_secret.into(),
+            app_id,
+            host: String::from(OPEN_PLATFORM_HOST),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// 替换接口域名，比如测试环境
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    fn nonce() -> String {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let now = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        format!("{}{}", now, COUNTER.fetch_add(1, Ordering::Relaxed))
+    }
+
+    async fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: Value,
+    ) -> Result<T, OpenPlatformError> {
+        let body = body.to_string().into_bytes();
+        let headers = sign_headers(
+            &self.access_key_id,
+            &self.access_key_secret,
+            &body,
+            chrono::Utc::now().timestamp(),
+            &Self::nonce(),
+        );
+        let mut req = self
+            .http
+            .post(format!("{}{}", self.host, path))
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json");
+        for (k, v) in headers {
+            req = req.header(k, v);
+        }
+        let resp: ApiResponse = req
+            .body(body)
+            .send()
+            .await
+            .map_err(OpenPlatformError::Http)?
+            .json()
+            .await
+            .map_err(OpenPlatformError::Http)?;
+        resp.into_data()
+    }
+
+    /// 用主播的身份码开始玩法
+    pub async fn start(&self, code: &str) -> Result<GameSession, OpenPlatformError> {
+        let data: StartData = self
+            .post(
+                "/v2/app/start",
+                json!({ "code": code, "app_id": self.app_id }),
+            )
+            .await?;
+        Ok(GameSession {
+            game_id: data.game_info.game_id,
+            auth_body: data.websocket_info.auth_body,
+            wss_link: data.websocket_info.wss_link,
+            anchor_info: data.anchor_info,
+        })
+    }
+
+    pub async fn heartbeat(&self, game_id: &str) -> Result<(), OpenPlatformError> {
+        self.post::<Value>("/v2/app/heartbeat", json!({ "game_id": game_id }))
+            .await
+            .map(drop)
+    }
+
+    pub async fn end(&self, game_id: &str) -> Result<(), OpenPlatformError> {
+        self.post::<Value>(
+            "/v2/app/end",
+            json!({ "game_id": game_id, "app_id": self.app_id }),
+        )
+        .await
+        .map(drop)
+    }
+
+    /// 在后台每[`GAME_HEARTBEAT_INTERVAL`]调用一次[`OpenPlatformClient::heartbeat`]，丢弃返回值不会停止
+    #[cfg(feature = "rt_tokio")]
+    pub fn spawn_heartbeat(&self, game_id: String) -> tokio::task::JoinHandle<()> {
+        let client = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(GAME_HEARTBEAT_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = client.heartbeat(&game_id).await {
+                    log::warn!("game {} heartbeat error: {}", game_id, e);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(feature = "rt_tokio")]
+impl GameSession {
+    pub async fn connect(&self) -> Result<crate::Connection, crate::ConnectError> {
+        self.connect_with(crate::ConnectConfig::default()).await
+    }
+
+    /// 依次尝试[`GameSession::wss_link`]，`config`中只有心跳、超时和缓冲区相关的参数有效
+    pub async fn connect_with(
+        &self,
+        config: crate::ConnectConfig,
+    ) -> Result<crate::Connection, crate::ConnectError> {
+        use crate::{connection::WsConnectError, ConnectError, Connection, Connector};
+        let room_id = self.anchor_info.room_id;
+        let connector = Connector::builder(room_id)
+            .anonymous()
+            .config(config)
+            .build()
+            .expect("anonymous connector needs no http api");
+        let mut auth_failed = false;
+        for link in &self.wss_link {
+            let url = match reqwest::Url::parse(link) {
+                Ok(url) => url,
+                Err(e) => {
+                    log::warn!("invalid wss link {}: {}", link, e);
+                    continue;
+                }
+            };
+            let auth = self.auth_body.clone().into_bytes();
+            match Connection::connect(url, room_id, auth, &connector).await {
+                Ok(stream) => return Ok(stream),
+                Err(WsConnectError::AuthFailed) => auth_failed = true,
+                Err(e) => log::warn!("connect error: {:?}", e),
+            }
+        }
+        if auth_failed {
+            Err(ConnectError::AuthFailed)
+        } else {
+            Err(ConnectError::HandshakeError)
+        }
+    }
+}
+
+fn open_medal(
+    room_id: u64,
+    guard_level: GuardLevel,
+    level: u64,
+    name: String,
+    wearing: bool,
+) -> Option<FansMedal> {
+    (wearing && level > 0).then_some(FansMedal {
+        anchor_roomid: room_id,
+        guard_level,
+        medal_level: level,
+        medal_name: name,
+    })
+}
+
+/// `LIVE_OPEN_PLATFORM_DM`
+#[derive(Debug, Deserialize)]
+pub(crate) struct OpenDanmaku {
+    room_id: u64,
+    #[serde(default)]
+    uid: u64,
+    #[serde(default)]
+    open_id: Option<String>,
+    uname: String,
+    uface: String,
+    msg: String,
+    msg_id: String,
+    #[serde(default)]
+    fans_medal_level: u64,
+    #[serde(default)]
+    fans_medal_name: String,
+    #[serde(default)]
+    fans_medal_wearing_status: bool,
+    #[serde(default)]
+    guard_level: GuardLevel,
+    timestamp: u64,
+    /// 0为普通弹幕，1为表情包弹幕
+    #[serde(default)]
+    dm_type: u64,
+    #[serde(default)]
+    emoji_img_url: String,
+}
+
+impl OpenDanmaku {
+    pub(crate) fn into_event(self) -> EventData {
+        let message = if self.dm_type == 1 {
+            DanmakuMessage::Emoticon {
+                emoticon: Emoticon {
+                    unique_id: self.msg.clone(),
+                    height: 0,
+                    width: 0,
+                    url: self.emoji_img_url,
+                },
+                alt_message: self.msg,
+            }
+        } else {
+            DanmakuMessage::Plain { message: self.msg }
+        };
+        DanmakuEvent {
+            id: Some(self.msg_id),
+            flag: DanmakuFlags::new(false, self.guard_level),
+            message,
+            user: User {
+                uid: self.uid,
+                uname: self.uname,
+                face: Some(self.uface),
+                level: None,
+                open_id: self.open_id,
+            },
+            fans_medal: open_medal(
+                self.room_id,
+                self.guard_level,
+                self.fans_medal_level,
+                self.fans_medal_name,
+                self.fans_medal_wearing_status,
+            ),
+            ts: self.timestamp,
+        }
+        .into()
+    }
+}
+
+/// `LIVE_OPEN_PLATFORM_SEND_GIFT`
+#[derive(Debug, Deserialize)]
+pub(crate) struct OpenGift {
+    room_id: u64,
+    #[serde(default)]
+    uid: u64,
+    #[serde(default)]
+    open_id: Option<String>,
+    uname: String,
+    uface: String,
+    gift_id: u64,
+    gift_name: String,
+    gift_num: u64,
+    /// 单价，1000为1元
+    price: u64,
+    /// 是否是付费礼物
+    paid: bool,
+    #[serde(default)]
+    fans_medal_level: u64,
+    #[serde(default)]
+    fans_medal_name: String,
+    #[serde(default)]
+    fans_medal_wearing_status: bool,
+    #[serde(default)]
+    guard_level: GuardLevel,
+    msg_id: String,
+}
+
+impl OpenGift {
+    pub(crate) fn into_event(self) -> EventData {
+        let coin_type = if self.paid {
+            CoinType::Gold
+        } else {
+            CoinType::Silver
+        };
+        GiftEvent {
+            user: User {
+                uid: self.uid,
+                uname: self.uname,
+                face: Some(self.uface),
+                level: None,
+                open_id: self.open_id,
+            },
+            fans_medal: open_medal(
+                self.room_id,
+                self.guard_level,
+                self.fans_medal_level,
+                self.fans_medal_name,
+                self.fans_medal_wearing_status,
+            ),
+            blindbox: None,
+            gift: Gift {
+                action: String::from("投喂"),
+                gift_name: self.gift_name,
+                gift_id: self.gift_id,
+                num: self.gift_num,
+                price: Coin::new(coin_type, self.price),
+                total: Coin::new(coin_type, self.price.saturating_mul(self.gift_num)),
+            },
+            rnd: self.msg_id,
+        }
+        .into()
+    }
+}
+
+/// `LIVE_OPEN_PLATFORM_SUPER_CHAT`
+#[derive(Debug, Deserialize)]
+pub(crate) struct OpenSuperChat {
+    room_id: u64,
+    #[serde(default)]
+    uid: u64,
+    #[serde(default)]
+    open_id: Option<String>,
+    uname: String,
+    uface: String,
+    message: String,
+    /// 单位为元
+    rmb: u64,
+    #[serde(default)]
+    fans_medal_level: u64,
+    #[serde(default)]
+    fans_medal_name: String,
+    #[serde(default)]
+    fans_medal_wearing_status: bool,
+    #[serde(default)]
+    guard_level: GuardLevel,
+}
+
+impl OpenSuperChat {
+    pub(crate) fn into_event(self) -> EventData {
+        SuperChatEvent {
+            user: User {
+                uid: self.uid,
+                uname: self.uname,
+                face: Some(self.uface),
+                level: None,
+                open_id: self.open_id,
+            },
+            fans_medal: open_medal(
+                self.room_id,
+                self.guard_level,
+                self.fans_medal_level,
+                self.fans_medal_name,
+                self.fans_medal_wearing_status,
+            ),
+            price: Money::from_cny(self.rmb),
+            message: self.message,
+            message_jpn: None,
+        }
+        .into()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenUserInfo {
+    #[serde(default)]
+    uid: u64,
+    #[serde(default)]
+    open_id: Option<String>,
+    uname: String,
+    uface: String,
+}
+
+/// `LIVE_OPEN_PLATFORM_GUARD`
+#[derive(Debug, Deserialize)]
+pub(crate) struct OpenGuard {
+    user_info: OpenUserInfo,
+    guard_level: GuardLevel,
+    /// 旧版协议中没有这个字段
+    #[serde(default)]
+    price: Money,
+}
+
+impl OpenGuard {
+    pub(crate) fn into_event(self) -> EventData {
+        GuardBuyEvent {
+            level: self.guard_level,
+            price: self.price,
+            user: User {
+                uid: self.user_info.uid,
+                uname: self.user_info.uname,
+                face: Some(self.user_info.uface),
+                level: None,
+                open_id: self.user_info.open_id,
+            },
+        }
+        .into()
+    }
+}
+
+/// `LIVE_OPEN_PLATFORM_LIKE`
+#[derive(Debug, Deserialize)]
+pub(crate) struct OpenLike {
+    room_id: u64,
+    #[serde(default)]
+    uid: u64,
+    #[serde(default)]
+    open_id: Option<String>,
+    uname: String,
+    uface: String,
+    like_text: String,
+    #[serde(default)]
+    fans_medal_level: u64,
+    #[serde(default)]
+    fans_medal_name: String,
+    #[serde(default)]
+    fans_medal_wearing_status: bool,
+}
+
+impl OpenLike {
+    pub(crate) fn into_event(self) -> EventData {
+        LikeEvent {
+            user: User {
+                uid: self.uid,
+                uname: self.uname,
+                face: Some(self.uface),
+                level: None,
+                open_id: self.open_id,
+            },
+            fans_medal: open_medal(
+                self.room_id,
+                GuardLevel::None,
+                self.fans_medal_level,
+                self.fans_medal_name,
+                self.fans_medal_wearing_status,
+            ),
+            like_text: self.like_text,
+        }
+        .into()
+    }
+}
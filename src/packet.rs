@@ -191,6 +191,26 @@ impl<'p> RawPacket<'p> {
         buffer
     }
 
+    pub fn opcode(&self) -> u32 {
+        self.head.opcode
+    }
+
+    /// 鉴权回复的包体为`{"code":0}`，code不为0或者无法解析表示鉴权失败
+    pub fn is_auth_success(&self) -> bool {
+        self.head.opcode != Operation::AuthReply as u32 || self.reply_code_ok()
+    }
+
+    /// 切换房间回复的包体和鉴权回复相同
+    pub fn is_change_room_success(&self) -> bool {
+        self.head.opcode == Operation::ChangeRoomReply as u32 && self.reply_code_ok()
+    }
+
+    fn reply_code_ok(&self) -> bool {
+        serde_json::from_slice::<serde_json::Value>(self.data.0)
+            .map(|reply| reply["code"].as_i64() == Some(0))
+            .unwrap_or(false)
+    }
+
     pub fn get_datas(self) -> Vec<Data> {
         match self.head.proto_code {
             // raw json
@@ -246,52 +266,56 @@ impl<'p> RawPacket<'p> {
     }
 }
 
+/// 数据包头中的操作码，值和协议中的一致
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
 pub enum Operation {
-    Handshake,
-    HandshakeReply,
-    Heartbeat,
-    HeartbeatReply,
-    SendMsg,
-    SendMsgReply,
-    DisconnectReply,
-    Auth,
-    AuthReply,
-    ProtoReady,
-    ProtoFinish,
-    ChangeRoom,
-    ChangeRoomReply,
-    Register,
-    RegisterReply,
-    Unregister,
-    UnregisterReply,
+    Handshake = 0,
+    HandshakeReply = 1,
+    Heartbeat = 2,
+    HeartbeatReply = 3,
+    SendMsg = 4,
+    SendMsgReply = 5,
+    DisconnectReply = 6,
+    Auth = 7,
+    AuthReply = 8,
+    Raw = 9,
+    ProtoReady = 10,
+    ProtoFinish = 11,
+    ChangeRoom = 12,
+    ChangeRoomReply = 13,
+    Register = 14,
+    RegisterReply = 15,
+    Unregister = 16,
+    UnregisterReply = 17,
 }
 
-use serde::Serialize;
-const PLATFORM_WEB: &str = "web";
 use crate::{
     cmd::CmdDeserError,
     event::{EventData, PopularityUpdateEvent},
 };
+use serde::Serialize;
 #[derive(Debug, Clone, Serialize)]
 pub struct Auth {
     pub uid: u64,
     pub roomid: u64,
     pub protover: i32,
-    pub platform: &'static str,
+    pub platform: String,
     pub r#type: i32,
     pub key: Option<String>,
 }
 
 impl Auth {
+    /// 使用[`crate::ConnectConfig`]的默认参数，需要修改参数时使用[`crate::ConnectorBuilder`]
     pub fn new(uid: u64, roomid: u64, key: Option<String>) -> Self {
+        let config = crate::ConnectConfig::default();
         Self {
             uid,
             roomid,
-            protover: 3,
-            platform: PLATFORM_WEB,
-            r#type: 2,
+            protover: config.protover,
+            platform: config.platform,
+            r#type: config.auth_type,
             key,
         }
     }
@@ -0,0 +1,185 @@
+//! PK 状态追踪
+//!
+//! [`PkTracker`] 消费事件流，维护某个直播间当前的PK状态和历史记录
+//!
+//!```no_run,ignore
+//!let mut tracker = PkTracker::new(connector.roomid);
+//!while let Some(evt) = stream.next().await {
+//!    if tracker.update(&evt) {
+//!        log::info!("{:?}", tracker.current());
+//!    }
+//!}
+//!```
+use crate::{event::*, model::PkSide};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PkStatus {
+    /// 进行中
+    Running,
+    /// 已结束，等待结算
+    Ended,
+    /// 已结算
+    Settled,
+}
+
+/// 从被追踪的直播间视角看到的PK状态
+/// - 时间均为秒级时间戳，如果是中途开始追踪的PK则为0
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PkState {
+    pub pk_id: u64,
+    pub status: PkStatus,
+    pub opponent_room_id: u64,
+    pub votes: u64,
+    pub opponent_votes: u64,
+    pub best_uname: String,
+    pub opponent_best_uname: String,
+    pub start_time: u64,
+    pub frozen_time: u64,
+    pub end_time: u64,
+    /// 胜方的房间号，平局或者未结束为`None`
+    pub winner: Option<u64>,
+}
+
+impl PkState {
+    fn new(pk_id: u64, ours: &PkSide, theirs: &PkSide) -> Self {
+        PkState {
+            pk_id,
+            status: PkStatus::Running,
+            opponent_room_id: theirs.room_id,
+            votes: ours.votes,
+            opponent_votes: theirs.votes,
+            best_uname: ours.best_uname.clone(),
+            opponent_best_uname: theirs.best_uname.clone(),
+            start_time: 0,
+            frozen_time: 0,
+            end_time: 0,
+            winner: None,
+        }
+    }
+
+    fn update_sides(&mut self, ours: &PkSide, theirs: &PkSide) {
+        self.votes = ours.votes;
+        self.opponent_votes = theirs.votes;
+        self.best_uname.clone_from(&ours.best_uname);
+        self.opponent_best_uname.clone_from(&theirs.best_uname);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.status != PkStatus::Running
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PkTracker {
+    room_id: u64,
+    current: Option<PkState>,
+    history: Vec<PkState>,
+}
+
+impl PkTracker {
+    /// `room_id` 需要是长房间号，即[`crate::Connector::roomid`]
+    pub fn new(room_id: u64) -> Self {
+        PkTracker {
+            room_id,
+            current: None,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn room_id(&self) -> u64 {
+        self.room_id
+    }
+
+    /// 正在进行或者等待结算的PK
+    pub fn current(&self) -> Option<&PkState> {
+        self.current.as_ref()
+    }
+
+    /// 已经结算或者被新的PK顶替的PK，按时间顺序排列
+    pub fn history(&self) -> &[PkState] {
+        &self.history
+    }
+
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    /// 根据事件更新状态，返回PK状态是否发生变化
+    ///
+    /// 来源不是被追踪的直播间的事件会被忽略
+    pub fn update(&mut self, event: &Event) -> bool {
+        if let Some(source) = &event.meta.source {
+            if source.room_id != self.room_id {
+                return false;
+            }
+        }
+        match &event.data {
+            EventData::PkStartEvent(evt) => {
+                let Some((ours, theirs)) = self.sides(&evt.init_info, &evt.match_info) else {
+                    return false;
+                };
+                let state = self.entry(evt.pk_id, ours, theirs);
+                state.start_time = evt.start_time;
+                state.frozen_time = evt.frozen_time;
+                state.end_time = evt.end_time;
+                true
+            }
+            EventData::PkProgressEvent(evt) => {
+                let Some((ours, theirs)) = self.sides(&evt.init_info, &evt.match_info) else {
+                    return false;
+                };
+                self.entry(evt.pk_id, ours, theirs)
+                    .update_sides(ours, theirs);
+                true
+            }
+            EventData::PkEndEvent(evt) => {
+                let Some((ours, theirs)) = self.sides(&evt.init_info, &evt.match_info) else {
+                    return false;
+                };
+                let state = self.entry(evt.pk_id, ours, theirs);
+                state.update_sides(ours, theirs);
+                state.status = PkStatus::Ended;
+                state.winner = evt.winner;
+                true
+            }
+            EventData::PkSettleEvent(evt) => match self.current.take() {
+                Some(mut state) if state.pk_id == evt.pk_id => {
+                    state.status = PkStatus::Settled;
+                    if evt.winner.is_some() {
+                        state.winner = evt.winner;
+                    }
+                    self.history.push(state);
+                    true
+                }
+                other => {
+                    self.current = other;
+                    false
+                }
+            },
+            _ => false,
+        }
+    }
+
+    /// 区分出本方和对方
+    fn sides<'a>(&self, a: &'a PkSide, b: &'a PkSide) -> Option<(&'a PkSide, &'a PkSide)> {
+        if a.room_id == self.room_id {
+            Some((a, b))
+        } else if b.room_id == self.room_id {
+            Some((b, a))
+        } else {
+            None
+        }
+    }
+
+    /// 获取`pk_id`对应的状态，如果是新的PK，旧的状态会被移入历史记录
+    fn entry(&mut self, pk_id: u64, ours: &PkSide, theirs: &PkSide) -> &mut PkState {
+        if let Some(state) = self.current.take() {
+            if state.pk_id == pk_id {
+                return self.current.insert(state);
+            }
+            self.history.push(state);
+        }
+        self.current.insert(PkState::new(pk_id, ours, theirs))
+    }
+}
@@ -0,0 +1,457 @@
+//! 录制和回放事件
+//!
+//! [`Recorder`]把事件按行写成json（jsonl），可以使用brotli压缩，也可以按场次分文件。
+//! [`RecordExt::record`]在单独的阻塞线程中写文件和压缩，不占用异步运行时。
+//! [`Replayer`]把录制的文件还原为事件流，按原速、倍速或者不等待回放，
+//! 事件的[`EventMeta::time`](crate::event::EventMeta::time)保持录制时的值
+//!
+//! 写入时定期刷新，没有新事件时写入线程也会按时刷新，
+//! 压缩流刷新时会写入同步点，程序崩溃后文件中已经刷新的部分仍然可以回放。
+//!
+//! 旧版本录制的文件中价格是不带单位的数字，回放时按[`crate::model::Money`]的兼容规则读取，
+//! 旧格式的礼物按[`crate::model::Gift`]的兼容规则读取
+//!
+//!```no_run,ignore
+//!let recorder = Recorder::new("./records").compress(true).rotate_per_session(true);
+//!let mut stream = connection.filter_map(|evt| async { evt.ok() }).record(recorder);
+//!
+//!let mut replay = Replayer::open("./records/21452505-20240101-200000.jsonl.br", ReplaySpeed::Scaled(10.0))?;
+//!while let Some(evt) = replay.next().await {
+//!    // 处理事件
+//!}
+//!```
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    future::Future,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::mpsc::{self, RecvTimeoutError},
+    task::{ready, Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures_util::Stream;
+use tokio::{task::JoinHandle, time::Sleep};
+
+use crate::event::{Event, EventData};
+
+/// 未压缩的文件扩展名
+pub const RECORD_EXTENSION: &str = "jsonl";
+/// brotli压缩的文件扩展名
+pub const COMPRESSED_RECORD_EXTENSION: &str = "jsonl.br";
+
+/// [`RecordExt::record`]中等待写入的事件数量上限，超出时丢弃事件
+pub const RECORD_BUFFER_SIZE: usize = 1024;
+
+/// 事件录制器
+///
+/// 文件名为`房间号-开始时间.jsonl`，时间为第一个事件的[`EventMeta::time`](crate::event::EventMeta::time)，
+/// 没有来源的事件使用`events`代替房间号
+///
+/// 写入是同步的，在异步代码中使用[`RecordExt::record`]
+pub struct Recorder {
+    dir: PathBuf,
+    compress: bool,
+    rotate_per_session: bool,
+    flush_every: usize,
+    flush_interval: Duration,
+    writer: Option<Box<dyn Write + Send>>,
+    path: Option<PathBuf>,
+    unflushed: usize,
+    last_flush: Instant,
+}
+
+impl Recorder {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Recorder {
+            dir: dir.into(),
+            compress: false,
+            rotate_per_session: false,
+            flush_every: 32,
+            flush_interval: Duration::from_secs(5),
+            writer: None,
+            path: None,
+            unflushed: 0,
+            last_flush: Instant::now(),
+        }
+    }
+
+    /// 使用brotli压缩
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// 收到[`crate::event::LiveStartEvent`]时开始新的文件，
+    /// 收到[`crate::event::LiveStopEvent`]时结束当前文件
+    pub fn rotate_per_session(mut self, rotate: bool) -> Self {
+        self.rotate_per_session = rotate;
+        self
+    }
+
+    /// 每写入多少个事件刷新一次，默认32，为1时每个事件都刷新
+    ///
+    /// 压缩时刷新太频繁会降低压缩率
+    pub fn flush_every(mut self, events: usize) -> Self {
+        self.flush_every = events.max(1);
+        self
+    }
+
+    /// 距离上一次刷新超过这个时间后刷新，默认5秒
+    ///
+    /// 直接调用[`Recorder::record`]时在写入下一个事件时检查，
+    /// 通过[`RecordExt::record`]写入时没有新事件也会按时刷新
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    /// 当前正在写入的文件
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    fn open(&mut self, event: &Event) -> io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let room = event
+            .meta
+            .source
+            .as_ref()
+            .map(|source| source.room_id.to_string())
+            .unwrap_or_else(|| String::from("events"));
+        let stem = format!("{}-{}", room, event.meta.time.format("%Y%m%d-%H%M%S"));
+        let extension = if self.compress {
+            COMPRESSED_RECORD_EXTENSION
+        } else {
+            RECORD_EXTENSION
+        };
+        // 同一秒内开始的文件加上序号，不追加到已有的文件
+        let mut index = 0;
+        let (file, path) = loop {
+            let name = match index {
+                0 => format!("{}.{}", stem, extension),
+                index => format!("{}-{}.{}", stem, index, extension),
+            };
+            let path = self.dir.join(name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => break (file, path),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => index += 1,
+                Err(e) => return Err(e),
+            }
+        };
+        let writer = BufWriter::new(file);
+        self.writer = Some(if self.compress {
+            Box::new(brotli::CompressorWriter::new(writer, 4096, 9, 22))
+        } else {
+            Box::new(writer)
+        });
+        self.path = Some(path);
+        Ok(())
+    }
+
+    /// 写入一个事件，需要时打开新的文件
+    pub fn record(&mut self, event: &Event) -> io::Result<()> {
+        if self.rotate_per_session && matches!(event.data, EventData::LiveStartEvent(_)) {
+            self.close()?;
+        }
+        if self.writer.is_none() {
+            self.open(event)?;
+        }
+        if let Some(writer) = self.writer.as_mut() {
+            serde_json::to_writer(&mut *writer, event)?;
+            writer.write_all(b"\n")?;
+            self.unflushed += 1;
+        }
+        if self.unflushed >= self.flush_every || self.last_flush.elapsed() >= self.flush_interval {
+            self.flush()?;
+        }
+        if self.rotate_per_session && matches!(event.data, EventData::LiveStopEvent(_)) {
+            self.close()?;
+        }
+        Ok(())
+    }
+
+    /// 有没有刷新的事件时，距离下一次按时刷新的时间
+    fn flush_due(&self) -> Option<Duration> {
+        (self.unflushed > 0).then(|| {
+            self.flush_interval
+                .saturating_sub(self.last_flush.elapsed())
+        })
+    }
+
+    /// 压缩时会写入同步点，之前写入的事件可以被完整解压
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.unflushed = 0;
+        self.last_flush = Instant::now();
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// 结束当前文件，之后的事件会写入新的文件
+    pub fn close(&mut self) -> io::Result<()> {
+        self.flush()?;
+        // 压缩流在drop时写入结尾
+        self.writer = None;
+        self.path = None;
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            log::warn!("failed to close record file: {}", e);
+        }
+    }
+}
+
+/// 见[`RecordExt::record`]
+pub struct Recording<S> {
+    inner: S,
+    /// 第一次轮询时移入写入线程
+    recorder: Option<Recorder>,
+    sender: Option<mpsc::SyncSender<Event>>,
+    writer: Option<JoinHandle<()>>,
+    ended: bool,
+}
+
+fn write_records(mut recorder: Recorder, receiver: mpsc::Receiver<Event>) {
+    loop {
+        // 有没有刷新的事件时最多等到下一次刷新
+        let received = match recorder.flush_due() {
+            Some(due) => receiver.recv_timeout(due),
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        let result = match received {
+            Ok(event) => recorder.record(&event),
+            Err(RecvTimeoutError::Timeout) => recorder.flush(),
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if let Err(e) = result {
+            log::warn!("failed to record event: {}", e);
+        }
+    }
+    if let Err(e) = recorder.close() {
+        log::warn!("failed to close record file: {}", e);
+    }
+}
+
+impl<S> Stream for Recording<S>
+where
+    S: Stream<Item = Event> + Unpin,
+{
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if !this.ended {
+            if let Some(recorder) = this.recorder.take() {
+                let (sender, receiver) = mpsc::sync_channel(RECORD_BUFFER_SIZE);
+                this.writer = Some(tokio::task::spawn_blocking(move || {
+                    write_records(recorder, receiver)
+                }));
+                this.sender = Some(sender);
+            }
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(event)) => {
+                    if let Some(sender) = &this.sender {
+                        if let Err(e) = sender.try_send(event.clone()) {
+                            log::warn!("drop event {} from record: {}", event.data.name(), e);
+                        }
+                    }
+                    return Poll::Ready(Some(event));
+                }
+                Poll::Ready(None) => {
+                    this.ended = true;
+                    // 关闭通道，写入线程写完剩下的事件后关闭文件
+                    this.sender = None;
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        if let Some(writer) = this.writer.as_mut() {
+            if let Err(e) = ready!(Pin::new(writer).poll(cx)) {
+                log::error!("record writer panicked: {}", e);
+            }
+            this.writer = None;
+        }
+        Poll::Ready(None)
+    }
+}
+
+pub trait RecordExt: Stream<Item = Event> + Sized {
+    /// 录制经过的每个事件，写入失败时只记录日志
+    ///
+    /// 需要在tokio运行时中轮询，事件流结束时等待文件写完再结束
+    fn record(self, recorder: Recorder) -> Recording<Self> {
+        Recording {
+            inner: self,
+            recorder: Some(recorder),
+            sender: None,
+            writer: None,
+            ended: false,
+        }
+    }
+}
+
+impl<S: Stream<Item = Event>> RecordExt for S {}
+
+/// 回放速度
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReplaySpeed {
+    /// 按录制时的间隔
+    #[default]
+    Original,
+    /// 间隔除以这个倍数，比如`2.0`为两倍速
+    Scaled(f64),
+    /// 不等待
+    Instant,
+}
+
+impl ReplaySpeed {
+    fn delay(self, elapsed: chrono::Duration) -> Duration {
+        let elapsed = elapsed.to_std().unwrap_or_default();
+        match self {
+            ReplaySpeed::Original => elapsed,
+            ReplaySpeed::Scaled(scale) if scale > 0.0 => elapsed.div_f64(scale),
+            ReplaySpeed::Scaled(_) | ReplaySpeed::Instant => Duration::ZERO,
+        }
+    }
+}
+
+/// 一次在阻塞线程中读取的事件数量
+const REPLAY_BATCH_SIZE: usize = 64;
+
+type RecordLines = io::Lines<Box<dyn BufRead + Send>>;
+
+/// 回放录制的事件
+///
+/// 按行读取，无法解析的行会被跳过，读取出错时结束。
+/// 读取和解压在阻塞线程中进行，需要在tokio运行时中轮询
+pub struct Replayer {
+    /// 在阻塞线程中读取时为`None`
+    lines: Option<RecordLines>,
+    reading: Option<JoinHandle<(RecordLines, Vec<Event>)>>,
+    buffered: VecDeque<Event>,
+    ended: bool,
+    speed: ReplaySpeed,
+    last_time: Option<chrono::DateTime<chrono::Utc>>,
+    sleep: Option<Pin<Box<Sleep>>>,
+    pending: Option<Event>,
+}
+
+impl Replayer {
+    pub fn new(reader: impl BufRead + Send + 'static, speed: ReplaySpeed) -> Self {
+        let reader: Box<dyn BufRead + Send> = Box::new(reader);
+        Replayer {
+            lines: Some(reader.lines()),
+            reading: None,
+            buffered: VecDeque::new(),
+            ended: false,
+            speed,
+            last_time: None,
+            sleep: None,
+            pending: None,
+        }
+    }
+
+    /// 以`.br`结尾的文件按brotli解压
+    pub fn open(path: impl AsRef<Path>, speed: ReplaySpeed) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let compressed = path.extension().is_some_and(|ext| ext == "br");
+        Ok(if compressed {
+            Replayer::new(BufReader::new(brotli::Decompressor::new(file, 4096)), speed)
+        } else {
+            Replayer::new(BufReader::new(file), speed)
+        })
+    }
+}
+
+fn next_event(lines: &mut RecordLines) -> Option<Event> {
+    loop {
+        let line = match lines.next()? {
+            Ok(line) => line,
+            Err(e) => {
+                log::warn!("failed to read record: {}", e);
+                return None;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Event>(&line) {
+            Ok(event) => return Some(event),
+            Err(e) => log::warn!("skip malformed record line: {}", e),
+        }
+    }
+}
+
+/// 最多读取[`REPLAY_BATCH_SIZE`]个事件，不足时说明已经读完
+fn read_events(lines: &mut RecordLines) -> Vec<Event> {
+    std::iter::from_fn(|| next_event(lines))
+        .take(REPLAY_BATCH_SIZE)
+        .collect()
+}
+
+impl Stream for Replayer {
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(sleep) = this.sleep.as_mut() {
+                if sleep.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                this.sleep = None;
+                return Poll::Ready(this.pending.take());
+            }
+            let Some(event) = this.buffered.pop_front() else {
+                if this.ended {
+                    return Poll::Ready(None);
+                }
+                let reading = match this.reading.as_mut() {
+                    Some(reading) => reading,
+                    None => {
+                        let Some(mut lines) = this.lines.take() else {
+                            return Poll::Ready(None);
+                        };
+                        this.reading.insert(tokio::task::spawn_blocking(move || {
+                            let events = read_events(&mut lines);
+                            (lines, events)
+                        }))
+                    }
+                };
+                let result = ready!(Pin::new(reading).poll(cx));
+                this.reading = None;
+                match result {
+                    Ok((lines, events)) => {
+                        this.ended = events.len() < REPLAY_BATCH_SIZE;
+                        this.lines = Some(lines);
+                        this.buffered.extend(events);
+                    }
+                    Err(e) => {
+                        log::error!("record reader panicked: {}", e);
+                        this.ended = true;
+                    }
+                }
+                continue;
+            };
+            let delay = this
+                .last_time
+                .map(|last| this.speed.delay(event.meta.time - last))
+                .unwrap_or_default();
+            this.last_time = Some(event.meta.time);
+            if delay.is_zero() {
+                return Poll::Ready(Some(event));
+            }
+            this.sleep = Some(Box::pin(tokio::time::sleep(delay)));
+            this.pending = Some(event);
+        }
+    }
+}
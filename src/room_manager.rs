@@ -0,0 +1,321 @@
+//! 多直播间连接管理
+//!
+//! [`RoomManager`] 在运行时添加、移除直播间，所有直播间共用一个客户端和登录信息，
+//! 事件合并到同一个[`RoomEvents`]流中，通过[`crate::event::EventMeta::source`]区分来源
+//!
+//!```no_run,ignore
+//!let (manager, mut events) = RoomManager::new(login_info);
+//!let room_id = manager.add_room(851181).await?;
+//!manager.add_room(21452505).await?;
+//!while let Some(evt) = events.next().await {
+//!    log::info!("{:?}: {:?}", evt.meta.source, evt.data);
+//!}
+//!```
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::Poll,
+    time::Duration,
+};
+
+use bilibili_client::{
+    api::live::danmu_info::RoomInfo,
+    reqwest_client::{Client, ClientError, LoginInfo},
+};
+use chrono::{DateTime, Utc};
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{mpsc, Semaphore},
+    task::JoinHandle,
+};
+
+use crate::{api::DanmuInfoApi, event::Event, Connection, Connector};
+
+const RECONNECT_DELAY_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(60);
+const EVENT_BUFFER_SIZE: usize = 1024;
+const DEFAULT_MAX_HANDSHAKES: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RoomStatus {
+    /// 第一次连接中
+    Connecting,
+    Connected,
+    /// 连接断开或者失败，等待重连
+    Reconnecting,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomHealth {
+    pub status: RoomStatus,
+    /// 长房间号
+    pub room_id: Option<u64>,
+    pub connected_at: Option<DateTime<Utc>>,
+    pub last_event_at: Option<DateTime<Utc>>,
+    pub event_count: u64,
+    pub reconnect_count: u64,
+    pub last_error: Option<String>,
+}
+
+impl Default for RoomHealth {
+    fn default() -> Self {
+        RoomHealth {
+            status: RoomStatus::Connecting,
+            room_id: None,
+            connected_at: None,
+            last_event_at: None,
+            event_count: 0,
+            reconnect_count: 0,
+            last_error: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RoomManagerError {
+    /// 不在tokio运行时中，无法启动连接任务
+    NoRuntime,
+    /// 无法解析为长房间号
+    ResolveFailed(String),
+    /// 直播间已经添加过，包含长房间号
+    AlreadyAdded(u64),
+}
+
+impl std::fmt::Display for RoomManagerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoomManagerError::NoRuntime => write!(f, "不在tokio运行时中"),
+            RoomManagerError::ResolveFailed(e) => write!(f, "解析房间号失败：{}", e),
+            RoomManagerError::AlreadyAdded(room_id) => write!(f, "直播间{}已经添加", room_id),
+        }
+    }
+}
+
+impl std::error::Error for RoomManagerError {}
+
+type SharedApi = Arc<dyn DanmuInfoApi<Error = ClientError> + Send + Sync>;
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 所有直播间任务共用的部分
+#[derive(Clone)]
+struct Shared {
+    client: Client,
+    api: SharedApi,
+    login_info: LoginInfo,
+    handshakes: Arc<Semaphore>,
+    tx: mpsc::Sender<Event>,
+}
+
+impl Shared {
+    /// `room`为添加时解析出的长房间号和主播uid，重连时不再解析
+    async fn connect(&self, room: &RoomInfo) -> Result<Connection, String> {
+        // 握手包括http请求和wss连接，全部计入并发限制
+        let _permit = self.handshakes.acquire().await.map_err(|e| e.to_string())?;
+        let connector = Connector::builder(room.room_id)
+            .login_info(self.login_info.clone())
+            .client(self.client.clone())
+            .resolve_room(false)
+            .uid(room.uid)
+            .init_with(self.api.clone())
+            .await
+            .map_err(|e| format!("{e:?}"))?;
+        connector.connect().await.map_err(|e| format!("{e:?}"))
+    }
+
+    async fn run_room(self, room: RoomInfo, health: Arc<Mutex<RoomHealth>>) {
+        let roomid = room.room_id;
+        let mut delay = RECONNECT_DELAY_MIN;
+        loop {
+            match self.connect(&room).await {
+                Ok(mut connection) => {
+                    delay = RECONNECT_DELAY_MIN;
+                    {
+                        let mut health = lock(&health);
+                        health.status = RoomStatus::Connected;
+                        health.connected_at = Some(Utc::now());
+                    }
+                    while let Some(item) = connection.next().await {
+                        match item {
+                            Ok(event) => {
+                                {
+                                    let mut health = lock(&health);
+                                    health.last_event_at = Some(event.meta.time);
+                                    health.event_count += 1;
+                                }
+                                if self.tx.send(event).await.is_err() {
+                                    // 事件流已经被丢弃
+                                    return;
+                                }
+                            }
+                            Err(e) => {
+                                lock(&health).last_error = Some(e.to_string());
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::warn!("room {roomid} connect error: {e}");
+                    lock(&health).last_error = Some(e);
+                }
+            }
+            {
+                let mut health = lock(&health);
+                health.status = RoomStatus::Reconnecting;
+                health.reconnect_count += 1;
+            }
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(RECONNECT_DELAY_MAX);
+        }
+    }
+}
+
+struct RoomEntry {
+    handle: JoinHandle<()>,
+    health: Arc<Mutex<RoomHealth>>,
+}
+
+struct Inner {
+    shared: Shared,
+    rooms: Mutex<HashMap<u64, RoomEntry>>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        for entry in lock(&self.rooms).values() {
+            entry.handle.abort();
+        }
+    }
+}
+
+/// 可以克隆，所有的克隆共享同一组连接，全部被丢弃后所有连接关闭
+#[derive(Clone)]
+pub struct RoomManager {
+    inner: Arc<Inner>,
+}
+
+/// [`RoomManager`] 合并后的事件流
+pub struct RoomEvents {
+    rx: mpsc::Receiver<Event>,
+}
+
+impl Stream for RoomEvents {
+    type Item = Event;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+impl RoomManager {
+    pub fn new(login_info: LoginInfo) -> (Self, RoomEvents) {
+        Self::with_client(Client::default(), login_info, DEFAULT_MAX_HANDSHAKES)
+    }
+
+    /// `max_handshakes` 为同时进行的握手数量上限
+    pub fn with_client(
+        client: Client,
+        login_info: LoginInfo,
+        max_handshakes: usize,
+    ) -> (Self, RoomEvents) {
+        client.set_login_info(&login_info);
+        let api = Arc::new(client.clone());
+        Self::with_api(api, client, login_info, max_handshakes)
+    }
+
+    /// 和[`RoomManager::with_client`]相同，但是http接口通过`api`请求
+    pub fn with_api(
+        api: Arc<dyn DanmuInfoApi<Error = ClientError> + Send + Sync>,
+        client: Client,
+        login_info: LoginInfo,
+        max_handshakes: usize,
+    ) -> (Self, RoomEvents) {
+        let (tx, rx) = mpsc::channel(EVENT_BUFFER_SIZE);
+        let shared = Shared {
+            client,
+            api,
+            login_info,
+            handshakes: Arc::new(Semaphore::new(max_handshakes.max(1))),
+            tx,
+        };
+        let manager = RoomManager {
+            inner: Arc::new(Inner {
+                shared,
+                rooms: Default::default(),
+            }),
+        };
+        (manager, RoomEvents { rx })
+    }
+
+    /// 添加直播间并在后台连接，断线后自动重连，需要在tokio运行时中调用
+    ///
+    /// 短号会先解析为长房间号，返回长房间号，之后的操作都使用长房间号。
+    /// 解析也计入握手的并发限制，只在添加时解析一次
+    pub async fn add_room(&self, roomid: u64) -> Result<u64, RoomManagerError> {
+        let runtime =
+            tokio::runtime::Handle::try_current().map_err(|_| RoomManagerError::NoRuntime)?;
+        let shared = &self.inner.shared;
+        let room = {
+            let _permit = shared
+                .handshakes
+                .acquire()
+                .await
+                .map_err(|e| RoomManagerError::ResolveFailed(e.to_string()))?;
+            shared
+                .api
+                .get_room_play_info(roomid)
+                .await
+                .map_err(|e| RoomManagerError::ResolveFailed(format!("{e:?}")))?
+        };
+        let room_id = room.room_id;
+        let mut rooms = lock(&self.inner.rooms);
+        if rooms.contains_key(&room_id) {
+            return Err(RoomManagerError::AlreadyAdded(room_id));
+        }
+        let health = Arc::new(Mutex::new(RoomHealth {
+            room_id: Some(room_id),
+            ..Default::default()
+        }));
+        let task = shared.clone().run_room(room, health.clone());
+        let handle = runtime.spawn(task);
+        rooms.insert(room_id, RoomEntry { handle, health });
+        Ok(room_id)
+    }
+
+    /// 移除直播间并关闭连接，如果直播间不存在，返回`false`
+    pub fn remove_room(&self, roomid: u64) -> bool {
+        match lock(&self.inner.rooms).remove(&roomid) {
+            Some(entry) => {
+                entry.handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 所有直播间的长房间号
+    pub fn rooms(&self) -> Vec<u64> {
+        lock(&self.inner.rooms).keys().copied().collect()
+    }
+
+    pub fn health(&self, roomid: u64) -> Option<RoomHealth> {
+        lock(&self.inner.rooms)
+            .get(&roomid)
+            .map(|entry| lock(&entry.health).clone())
+    }
+
+    pub fn health_all(&self) -> HashMap<u64, RoomHealth> {
+        lock(&self.inner.rooms)
+            .iter()
+            .map(|(roomid, entry)| (*roomid, lock(&entry.health).clone()))
+            .collect()
+    }
+}
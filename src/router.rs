@@ -0,0 +1,413 @@
+//! 机器人的事件分发
+//!
+//! [`Router`]从事件流中取出事件，依次经过[`Middleware`]，再交给匹配的[`Handler`]。
+//! 每个处理器在单独的任务中运行，通过[`Context`]拿到原始事件和[`Connector`]来回复弹幕
+//!
+//!```no_run,ignore
+//!let router = Router::new()
+//!    .middleware(Logger)
+//!    .middleware(RateLimit::per_user(Duration::from_secs(5)).commands_only())
+//!    .on(|evt: DanmakuEvent, _ctx| async move {
+//!        log::info!("{}", evt.message);
+//!        Ok(())
+//!    })
+//!    .command("ping", |_cmd, ctx: Context| async move {
+//!        ctx.reply("pong").await?;
+//!        Ok(())
+//!    })
+//!    .route(Handler::on(save_gift).concurrency(1));
+//!let stream = connection.filter_map(|evt| async { evt.ok() });
+//!router.run(stream, connector).await;
+//!```
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures_util::{future::BoxFuture, Stream, StreamExt};
+use tokio::{
+    sync::{mpsc, Semaphore},
+    task::JoinSet,
+};
+
+use crate::{
+    event::{Event, EventData, EventType},
+    filter::EventFilter,
+    model::DanmakuMessage,
+    send::{DanmakuOptions, SendDanmakuError},
+    Connector,
+};
+
+pub type HandlerError = Box<dyn std::error::Error + Send + Sync>;
+pub type HandlerResult = Result<(), HandlerError>;
+
+/// `!name args...`形式的弹幕命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    pub name: String,
+    /// 按空白分隔的参数
+    pub args: Vec<String>,
+}
+
+impl Command {
+    pub fn parse(prefix: &str, text: &str) -> Option<Self> {
+        let mut parts = text.trim().strip_prefix(prefix)?.split_whitespace();
+        let name = parts.next()?.to_owned();
+        Some(Command {
+            name,
+            args: parts.map(str::to_owned).collect(),
+        })
+    }
+
+    pub fn arg(&self, index: usize) -> Option<&str> {
+        self.args.get(index).map(String::as_str)
+    }
+}
+
+/// 处理器和中间件的上下文
+#[derive(Clone)]
+pub struct Context {
+    pub connector: Connector,
+    pub event: Arc<Event>,
+    /// 弹幕是命令时解析出的[`Command`]
+    pub command: Option<Command>,
+}
+
+impl Context {
+    /// 在当前直播间发送弹幕，事件有发起用户时回复这个用户
+    pub async fn reply(&self, msg: &str) -> Result<(), SendDanmakuError> {
+        let mut options = DanmakuOptions::default();
+        if let Some(user) = self.event.data.user() {
+            options = options.reply_to(user.uid);
+        }
+        self.connector.send_danmaku(msg, &options).await
+    }
+}
+
+/// 在分发之前检查事件，返回`false`时事件不再交给处理器
+pub trait Middleware: Send + Sync + 'static {
+    fn handle(&self, ctx: &Context) -> bool;
+}
+
+impl<F> Middleware for F
+where
+    F: Fn(&Context) -> bool + Send + Sync + 'static,
+{
+    fn handle(&self, ctx: &Context) -> bool {
+        self(ctx)
+    }
+}
+
+/// 只分发满足过滤条件的事件
+impl Middleware for EventFilter {
+    fn handle(&self, ctx: &Context) -> bool {
+        self.matches(&ctx.event)
+    }
+}
+
+/// 记录每个事件
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Logger;
+
+impl Middleware for Logger {
+    fn handle(&self, ctx: &Context) -> bool {
+        match ctx.event.data.user() {
+            Some(user) => log::info!(
+                "{} from {}({})",
+                ctx.event.data.name(),
+                user.uname,
+                user.uid
+            ),
+            None => log::info!("{}", ctx.event.data.name()),
+        }
+        true
+    }
+}
+
+/// 按用户限流，同一个用户在`interval`内只有第一个事件会被分发
+///
+/// 没有发起用户的事件不受限制
+#[derive(Debug)]
+pub struct RateLimit {
+    interval: Duration,
+    commands_only: bool,
+    last: Mutex<HashMap<u64, Instant>>,
+}
+
+impl RateLimit {
+    /// 超过这个数量时清理过期的记录
+    const PRUNE_THRESHOLD: usize = 1024;
+
+    pub fn per_user(interval: Duration) -> Self {
+        RateLimit {
+            interval,
+            commands_only: false,
+            last: Mutex::default(),
+        }
+    }
+
+    /// 只限制命令，其他事件不受影响
+    pub fn commands_only(mut self) -> Self {
+        self.commands_only = true;
+        self
+    }
+}
+
+impl Middleware for RateLimit {
+    fn handle(&self, ctx: &Context) -> bool {
+        if self.commands_only && ctx.command.is_none() {
+            return true;
+        }
+        let Some(user) = ctx.event.data.user() else {
+            return true;
+        };
+        let now = Instant::now();
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        if last.len() > Self::PRUNE_THRESHOLD {
+            last.retain(|_, time| now.duration_since(*time) < self.interval);
+        }
+        match last.get(&user.uid) {
+            Some(time) if now.duration_since(*time) < self.interval => false,
+            _ => {
+                last.insert(user.uid, now);
+                true
+            }
+        }
+    }
+}
+
+type ErasedHandler = dyn Fn(&Context) -> Option<BoxFuture<'static, HandlerResult>> + Send + Sync;
+
+/// 事件处理器，处理器返回的错误会被记录到日志
+pub struct Handler {
+    name: Cow<'static, str>,
+    call: Box<ErasedHandler>,
+    concurrency: Option<usize>,
+}
+
+impl Handler {
+    /// 处理类型为`T`的事件
+    pub fn on<T, Fut>(handler: impl Fn(T, Context) -> Fut + Send + Sync + 'static) -> Self
+    where
+        T: EventType + Clone + Send + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static,
+    {
+        let name = std::any::type_name::<T>();
+        Handler {
+            name: Cow::Borrowed(name.rsplit("::").next().unwrap_or(name)),
+            call: Box::new(move |ctx| {
+                let event = T::from_data(&ctx.event.data)?.clone();
+                Some(Box::pin(handler(event, ctx.clone())))
+            }),
+            concurrency: None,
+        }
+    }
+
+    /// 处理名称为`name`的命令，不区分大小写
+    pub fn command<Fut>(
+        name: impl Into<String>,
+        handler: impl Fn(Command, Context) -> Fut + Send + Sync + 'static,
+    ) -> Self
+    where
+        Fut: Future<Output = HandlerResult> + Send + 'static,
+    {
+        let name = name.into();
+        Handler {
+            name: Cow::Owned(format!("command {}", name)),
+            call: Box::new(move |ctx| {
+                let command = ctx.command.as_ref()?;
+                if !command.name.eq_ignore_ascii_case(&name) {
+                    return None;
+                }
+                Some(Box::pin(handler(command.clone(), ctx.clone())))
+            }),
+            concurrency: None,
+        }
+    }
+
+    /// 同时运行的数量上限，默认不限制
+    ///
+    /// 达到上限时这个处理器的事件在队列中等待，按到达的顺序交给处理器，
+    /// 不影响其他处理器和后面事件的分发。队列没有长度限制
+    pub fn concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = Some(limit.max(1));
+        self
+    }
+}
+
+/// 限制了并发数量的处理器的队列
+type RouteQueue = mpsc::UnboundedSender<BoxFuture<'static, ()>>;
+
+/// 见[模块文档](self)
+pub struct Router {
+    prefixes: Vec<String>,
+    middlewares: Vec<Box<dyn Middleware>>,
+    routes: Vec<Handler>,
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Router {
+            prefixes: vec![String::from("!"), String::from("！")],
+            middlewares: vec![],
+            routes: vec![],
+        }
+    }
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 命令的前缀，默认为`!`和全角的`！`
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefixes = vec![prefix.into()];
+        self
+    }
+
+    /// 中间件按添加的顺序执行
+    pub fn middleware(mut self, middleware: impl Middleware) -> Self {
+        self.middlewares.push(Box::new(middleware));
+        self
+    }
+
+    pub fn route(mut self, handler: Handler) -> Self {
+        self.routes.push(handler);
+        self
+    }
+
+    /// 见[`Handler::on`]
+    pub fn on<T, Fut>(self, handler: impl Fn(T, Context) -> Fut + Send + Sync + 'static) -> Self
+    where
+        T: EventType + Clone + Send + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static,
+    {
+        self.route(Handler::on(handler))
+    }
+
+    /// 见[`Handler::command`]
+    pub fn command<Fut>(
+        self,
+        name: impl Into<String>,
+        handler: impl Fn(Command, Context) -> Fut + Send + Sync + 'static,
+    ) -> Self
+    where
+        Fut: Future<Output = HandlerResult> + Send + 'static,
+    {
+        self.route(Handler::command(name, handler))
+    }
+
+    fn parse_command(&self, event: &Event) -> Option<Command> {
+        match &event.data {
+            EventData::DanmakuEvent(evt) => match &evt.message {
+                DanmakuMessage::Plain { message } => self
+                    .prefixes
+                    .iter()
+                    .find_map(|prefix| Command::parse(prefix, message)),
+                DanmakuMessage::Emoticon { .. } => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn dispatch(
+        &self,
+        event: Event,
+        connector: &Connector,
+        queues: &[Option<RouteQueue>],
+        tasks: &mut JoinSet<()>,
+    ) {
+        let ctx = Context {
+            connector: connector.clone(),
+            command: self.parse_command(&event),
+            event: Arc::new(event),
+        };
+        if !self
+            .middlewares
+            .iter()
+            .all(|middleware| middleware.handle(&ctx))
+        {
+            return;
+        }
+        for (handler, queue) in self.routes.iter().zip(queues) {
+            let Some(task) = (handler.call)(&ctx) else {
+                continue;
+            };
+            let name = handler.name.clone();
+            let task = Box::pin(async move {
+                if let Err(e) = task.await {
+                    log::warn!("handler {} failed: {}", name, e);
+                }
+            });
+            match queue {
+                // 队列的任务在结束前不会关闭接收端
+                Some(queue) => {
+                    let _ = queue.send(task);
+                }
+                None => {
+                    tasks.spawn(task);
+                }
+            }
+        }
+    }
+
+    /// 分发事件直到事件流结束，然后等待还在运行的处理器
+    pub async fn run<S>(self, mut stream: S, connector: Connector)
+    where
+        S: Stream<Item = Event> + Unpin,
+    {
+        let mut tasks = JoinSet::new();
+        let queues: Vec<Option<RouteQueue>> = self
+            .routes
+            .iter()
+            .map(|handler| {
+                let limit = handler.concurrency?;
+                let (queue, receiver) = mpsc::unbounded_channel();
+                tasks.spawn(run_queue(receiver, limit));
+                Some(queue)
+            })
+            .collect();
+        while let Some(event) = stream.next().await {
+            self.dispatch(event, &connector, &queues, &mut tasks);
+            while let Some(result) = tasks.try_join_next() {
+                log_join_error(result);
+            }
+        }
+        // 关闭队列，队列的任务处理完剩下的事件后结束
+        drop(queues);
+        while let Some(result) = tasks.join_next().await {
+            log_join_error(result);
+        }
+    }
+}
+
+/// 按顺序取出一个处理器的事件，拿到许可后再启动，同时运行的数量不超过`limit`
+async fn run_queue(mut receiver: mpsc::UnboundedReceiver<BoxFuture<'static, ()>>, limit: usize) {
+    let permits = Arc::new(Semaphore::new(limit));
+    let mut tasks = JoinSet::new();
+    while let Some(task) = receiver.recv().await {
+        let Ok(permit) = permits.clone().acquire_owned().await else {
+            break;
+        };
+        tasks.spawn(async move {
+            let _permit = permit;
+            task.await;
+        });
+        while let Some(result) = tasks.try_join_next() {
+            log_join_error(result);
+        }
+    }
+    while let Some(result) = tasks.join_next().await {
+        log_join_error(result);
+    }
+}
+
+fn log_join_error(result: Result<(), tokio::task::JoinError>) {
+    if let Err(e) = result {
+        log::error!("handler panicked: {}", e);
+    }
+}
@@ -0,0 +1,267 @@
+//! 通过[`Connector`]发送弹幕
+//!
+//! 使用连接器中已经登录的客户端和长房间号，csrf取自登录信息，见[`Connector::csrf`]
+//!
+//!```no_run,ignore
+//!let connector = Connector::builder(roomid).login_info(login_info).init().await?;
+//!let options = DanmakuOptions::default().mode(DanmakuMode::Top).color(0xff6868);
+//!connector.send_danmaku("你好", &options).await?;
+//!```
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{ApiError, ApiResponse},
+    Connector,
+};
+
+pub const SEND_DANMAKU_URL: &str = "https://api.live.bilibili.com/msg/send";
+
+/// 同一个账号在同一个直播间两次发送弹幕的最小间隔，小于这个间隔大概率会被服务器拒绝
+pub const DANMAKU_MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 普通用户的弹幕长度上限，按字符计，等级和大航海会提高上限
+pub const DANMAKU_DEFAULT_MAX_LENGTH: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum DanmakuMode {
+    #[default]
+    Scroll = 1,
+    Bottom = 4,
+    Top = 5,
+}
+
+/// - `color` 为RGB颜色，比如`0xffffff`
+/// - `reply_to` 为回复的用户的uid
+/// - `max_length` 为本地检查的长度上限，超出时不会发送
+#[derive(Debug, Clone)]
+pub struct DanmakuOptions {
+    pub color: u32,
+    pub mode: DanmakuMode,
+    pub font_size: u32,
+    pub reply_to: Option<u64>,
+    pub max_length: usize,
+}
+
+impl Default for DanmakuOptions {
+    fn default() -> Self {
+        DanmakuOptions {
+            color: 0xffffff,
+            mode: DanmakuMode::default(),
+            font_size: 25,
+            reply_to: None,
+            max_length: DANMAKU_DEFAULT_MAX_LENGTH,
+        }
+    }
+}
+
+impl DanmakuOptions {
+    pub fn color(mut self, color: u32) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn mode(mut self, mode: DanmakuMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn font_size(mut self, font_size: u32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    pub fn reply_to(mut self, uid: u64) -> Self {
+        self.reply_to = Some(uid);
+        self
+    }
+
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum SendDanmakuError {
+    Empty,
+    TooLong {
+        len: usize,
+        max: usize,
+    },
+    /// 本地限流，距离这个账号在这个直播间上一次发送不足[`DANMAKU_MIN_INTERVAL`]
+    RateLimited {
+        retry_after: Duration,
+    },
+    /// 登录信息中没有`bili_jct`，见[`Connector::csrf`]
+    MissingCsrf,
+    /// 10030、10031，发送频率过快
+    TooFast,
+    /// 1003，被禁言
+    Muted,
+    /// -101，未登录
+    NotLoggedIn,
+    /// -400，请求参数错误
+    BadRequest {
+        message: String,
+    },
+    Api {
+        code: i64,
+        message: String,
+    },
+    Http(reqwest::Error),
+}
+
+impl std::fmt::Display for SendDanmakuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use SendDanmakuError::*;
+        match self {
+            Empty => write!(f, "弹幕为空"),
+            TooLong { len, max } => write!(f, "弹幕过长：{}/{}", len, max),
+            RateLimited { retry_after } => write!(f, "发送过快，{:?}后重试", retry_after),
+            MissingCsrf => write!(f, "缺少csrf"),
+            TooFast => write!(f, "发送频率过快"),
+            Muted => write!(f, "你被禁言啦"),
+            NotLoggedIn => write!(f, "未登录"),
+            BadRequest { message } => write!(f, "请求错误：{}", message),
+            Api { code, message } => write!(f, "发送失败[{}]：{}", code, message),
+            Http(e) => write!(f, "http错误：{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SendDanmakuError {}
+
+impl From<ApiError> for SendDanmakuError {
+    fn from(e: ApiError) -> Self {
+        match e {
+            ApiError::NotLoggedIn => SendDanmakuError::NotLoggedIn,
+            ApiError::BadRequest { message } => SendDanmakuError::BadRequest { message },
+            ApiError::Api { code, message } => SendDanmakuError::Api { code, message },
+        }
+    }
+}
+
+impl SendDanmakuError {
+    pub(crate) fn from_code(code: i64, message: String) -> Option<Self> {
+        match code {
+            10030 | 10031 => Some(SendDanmakuError::TooFast),
+            1003 => Some(SendDanmakuError::Muted),
+            code => ApiResponse::check(code, message).err().map(Self::from),
+        }
+    }
+}
+
+pub(crate) fn check_message(msg: &str, max_length: usize) -> Result<(), SendDanmakuError> {
+    let len = msg.chars().count();
+    if msg.trim().is_empty() {
+        Err(SendDanmakuError::Empty)
+    } else if len > max_length {
+        Err(SendDanmakuError::TooLong {
+            len,
+            max: max_length,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// 上一次发送弹幕的时间，见[`Connector::reserve_danmaku_slot`]
+pub(crate) type DanmakuSlot = Arc<Mutex<Option<DateTime<Utc>>>>;
+
+/// 占用的发送机会，发送失败时通过[`Connector::release_danmaku_slot`]归还
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Reservation {
+    reserved: DateTime<Utc>,
+    previous: Option<DateTime<Utc>>,
+}
+
+impl Connector {
+    /// 占用一次发送机会，距离上一次发送不足[`DANMAKU_MIN_INTERVAL`]时返回需要等待的时间
+    pub(crate) fn reserve_danmaku_slot(&self) -> Result<Reservation, SendDanmakuError> {
+        let mut last = self.last_danmaku.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Utc::now();
+        if let Some(elapsed) = last.and_then(|last| (now - last).to_std().ok()) {
+            if elapsed < DANMAKU_MIN_INTERVAL {
+                return Err(SendDanmakuError::RateLimited {
+                    retry_after: DANMAKU_MIN_INTERVAL - elapsed,
+                });
+            }
+        }
+        let previous = last.replace(now);
+        Ok(Reservation {
+            reserved: now,
+            previous,
+        })
+    }
+
+    /// 归还没有用掉的发送机会，之后已经有新的发送时不做处理
+    pub(crate) fn release_danmaku_slot(&self, reservation: Reservation) {
+        let mut last = self.last_danmaku.lock().unwrap_or_else(|e| e.into_inner());
+        if *last == Some(reservation.reserved) {
+            *last = reservation.previous;
+        }
+    }
+
+    /// 在当前直播间发送弹幕
+    ///
+    /// 本地检查长度和发送间隔，不满足时不会请求接口。
+    /// 发送间隔由这个连接器和它的克隆共享，请求失败时不占用发送机会
+    pub async fn send_danmaku(
+        &self,
+        msg: &str,
+        options: &DanmakuOptions,
+    ) -> Result<(), SendDanmakuError> {
+        check_message(msg, options.max_length)?;
+        let csrf = self.csrf().ok_or(SendDanmakuError::MissingCsrf)?.to_owned();
+        let reservation = self.reserve_danmaku_slot()?;
+        let result = self.post_danmaku(msg, options, csrf).await;
+        // 服务器认为发送过快时保留这次的间隔
+        if matches!(&result, Err(e) if !matches!(e, SendDanmakuError::TooFast)) {
+            self.release_danmaku_slot(reservation);
+        }
+        result
+    }
+
+    async fn post_danmaku(
+        &self,
+        msg: &str,
+        options: &DanmakuOptions,
+        csrf: String,
+    ) -> Result<(), SendDanmakuError> {
+        let mut form = vec![
+            ("bubble", String::from("0")),
+            ("msg", msg.to_owned()),
+            ("color", options.color.to_string()),
+            ("mode", (options.mode as u32).to_string()),
+            ("fontsize", options.font_size.to_string()),
+            ("rnd", Utc::now().timestamp().to_string()),
+            ("roomid", self.roomid.to_string()),
+            ("csrf", csrf.clone()),
+            ("csrf_token", csrf),
+        ];
+        if let Some(uid) = options.reply_to {
+            form.push(("reply_mid", uid.to_string()));
+        }
+        let resp: ApiResponse = self
+            .client()
+            .inner()
+            .post(SEND_DANMAKU_URL)
+            .form(&form)
+            .send()
+            .await
+            .map_err(SendDanmakuError::Http)?
+            .json()
+            .await
+            .map_err(SendDanmakuError::Http)?;
+        match SendDanmakuError::from_code(resp.code, resp.message) {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
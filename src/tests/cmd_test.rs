@@ -22,3 +22,510 @@ fn stop_list_test() {
     let cmd = Cmd::deser(json_val).expect("cmd deser error");
     dbg!(cmd);
 }
+
+#[test]
+fn anchor_lot_test() {
+    use crate::{event::EventData, model::Money};
+    let event = |json: &str| {
+        let json_val = serde_json::from_str(json).expect("json parse error");
+        Cmd::deser(json_val)
+            .expect("cmd deser error")
+            .into_event()
+            .expect("lottery event")
+    };
+    let EventData::AnchorLotteryStartEvent(start) =
+        event(include_str!("./mock/cmd/AnchorLotStart.json"))
+    else {
+        unreachable!("should be AnchorLotteryStartEvent")
+    };
+    assert_eq!(start.id, 2942451);
+    assert_eq!(start.award_name, "5元红包");
+    assert_eq!(start.danmu, "主播好帅");
+    assert_eq!(start.require_text, "当前主播粉丝勋章至少1级");
+    assert_eq!((start.max_time, start.time), (600, 599));
+    let gift = start.gift.expect("gift requirement");
+    assert_eq!((gift.gift_id, gift.gift_num), (31036, 1));
+    assert_eq!(gift.gift_name, "小花花");
+    assert_eq!(gift.gift_price, Money::from_gold(100));
+
+    assert!(matches!(
+        event(include_str!("./mock/cmd/AnchorLotCheckstatus.json")),
+        EventData::AnchorLotteryCheckEvent(check) if check.id == 2942451 && check.status == 4
+    ));
+
+    let EventData::AnchorLotteryAwardEvent(award) =
+        event(include_str!("./mock/cmd/AnchorLotAward.json"))
+    else {
+        unreachable!("should be AnchorLotteryAwardEvent")
+    };
+    assert_eq!(award.id, 2942451);
+    assert_eq!(award.award_num, 1);
+    assert_eq!(award.winners.len(), 1);
+    assert_eq!(award.winners[0].uid, 33778290);
+    assert_eq!(award.winners[0].uname, "ASD设计");
+}
+
+#[test]
+fn red_pocket_test() {
+    use crate::event::EventData;
+    let json = include_str!("./mock/cmd/PopularityRedPocketStart.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let cmd = Cmd::deser(json_val).expect("cmd deser error");
+    assert!(matches!(
+        cmd.into_event(),
+        Some(EventData::RedPocketStartEvent(_))
+    ));
+    let json = include_str!("./mock/cmd/PopularityRedPocketWinnerList.json");
+    let mut json_val: serde_json::Value = serde_json::from_str(json).expect("json parse error");
+    let cmd = Cmd::deser(json_val.clone()).expect("cmd deser error");
+    let Some(EventData::RedPocketWinnerEvent(evt)) = cmd.into_event() else {
+        unreachable!("should be RedPocketWinnerEvent")
+    };
+    assert_eq!(evt.winners.len(), 3);
+    let award = evt.winners[0].award.as_ref().expect("award");
+    assert_eq!((award.award_id, award.award_name.as_str()), (31251, "干杯"));
+    // 奖品列表中没有的礼物保留中奖用户，格式不对的行被丢弃
+    json_val["data"]["awards"]
+        .as_object_mut()
+        .expect("awards")
+        .remove("31251");
+    json_val["data"]["winner_info"]
+        .as_array_mut()
+        .expect("winner info")
+        .push(serde_json::json!(["bad row"]));
+    let cmd = Cmd::deser(json_val).expect("cmd deser error");
+    let Some(EventData::RedPocketWinnerEvent(evt)) = cmd.into_event() else {
+        unreachable!("should be RedPocketWinnerEvent")
+    };
+    assert_eq!(evt.winners.len(), 3);
+    assert_eq!(evt.winners[0].user.uid, 33778290);
+    assert!(evt.winners[0].award.is_none());
+    assert!(evt.winners[1].award.is_some());
+}
+
+#[test]
+fn pk_test() {
+    use crate::event::{Event, EventMeta};
+    use crate::pk::{PkStatus, PkTracker};
+    let mut tracker = PkTracker::new(21452505);
+    for json in [
+        include_str!("./mock/cmd/PkBattleStartNew.json"),
+        include_str!("./mock/cmd/PkBattleProcessNew.json"),
+        include_str!("./mock/cmd/PkBattleEnd.json"),
+    ] {
+        let json_val = serde_json::from_str(json).expect("json parse error");
+        let cmd = Cmd::deser(json_val).expect("cmd deser error");
+        let data = cmd.into_event().expect("pk event");
+        assert!(tracker.update(&Event {
+            data,
+            meta: EventMeta::new(),
+        }));
+    }
+    let state = tracker.current().expect("pk state");
+    assert_eq!(state.pk_id, 303476352);
+    assert_eq!(state.opponent_room_id, 8765806);
+    assert_eq!((state.votes, state.opponent_votes), (100, 1520));
+    assert_eq!(state.status, PkStatus::Ended);
+    assert_eq!(state.winner, Some(8765806));
+
+    let json = include_str!("./mock/cmd/PkBattleSettleV2.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let cmd = Cmd::deser(json_val).expect("cmd deser error");
+    let data = cmd.into_event().expect("pk event");
+    assert!(tracker.update(&Event {
+        data,
+        meta: EventMeta::new(),
+    }));
+    assert!(tracker.current().is_none());
+    assert_eq!(tracker.history().len(), 1);
+    assert_eq!(tracker.history()[0].status, PkStatus::Settled);
+}
+
+#[test]
+fn like_test() {
+    use crate::event::EventData;
+    let json = include_str!("./mock/cmd/LikeInfoV3Click.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let cmd = Cmd::deser(json_val).expect("cmd deser error");
+    assert!(matches!(
+        cmd.into_event(),
+        Some(EventData::LikeEvent(evt)) if evt.user.uid == 33778290 && evt.fans_medal.is_some()
+    ));
+    let json = include_str!("./mock/cmd/LikeInfoV3Update.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let cmd = Cmd::deser(json_val).expect("cmd deser error");
+    assert!(matches!(
+        cmd.into_event(),
+        Some(EventData::LikeCountUpdateEvent(evt)) if evt.click_count == 18735
+    ));
+}
+
+#[test]
+fn interact_word_test() {
+    use crate::event::EventData;
+    use crate::model::FollowKind;
+    let json = include_str!("./mock/cmd/InteractWord.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let cmd = Cmd::deser(json_val).expect("cmd deser error");
+    assert!(matches!(
+        cmd.into_event(),
+        Some(EventData::EnterRoomEvent(evt)) if evt.timestamp == 1651240277
+    ));
+    let json = include_str!("./mock/cmd/InteractWordFollow.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let cmd = Cmd::deser(json_val).expect("cmd deser error");
+    assert!(matches!(
+        cmd.into_event(),
+        Some(EventData::FollowEvent(evt)) if evt.kind == FollowKind::Follow && evt.fans_medal.is_none()
+    ));
+    // 旧版协议没有msg_type，未知的msg_type也作为进入直播间
+    let mut json_val: serde_json::Value = serde_json::from_str(json).expect("json parse error");
+    for msg_type in [None, Some(99)] {
+        let data = json_val["data"]
+            .as_object_mut()
+            .expect("data is not object");
+        match msg_type {
+            Some(msg_type) => data.insert(String::from("msg_type"), msg_type.into()),
+            None => data.remove("msg_type"),
+        };
+        let cmd = Cmd::deser(json_val.clone()).expect("cmd deser error");
+        assert!(matches!(
+            cmd.into_event(),
+            Some(EventData::EnterRoomEvent(_))
+        ));
+    }
+}
+
+#[test]
+fn notice_test() {
+    use crate::event::EventData;
+    use crate::model::NoticeKind;
+    let json = include_str!("./mock/cmd/EntryEffect.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let cmd = Cmd::deser(json_val).expect("cmd deser error");
+    assert!(matches!(
+        cmd.into_event(),
+        Some(EventData::EntryEffectEvent(evt)) if evt.uid == 3780985 && evt.privilege_type == crate::model::GuardLevel::Captain
+    ));
+    let json = include_str!("./mock/cmd/NoticeMsg.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let cmd = Cmd::deser(json_val).expect("cmd deser error");
+    assert!(matches!(
+        cmd.into_event(),
+        Some(EventData::NoticeEvent(evt)) if evt.kind == NoticeKind::Broadcast && evt.room_id == Some(22894962)
+    ));
+    let json = include_str!("./mock/cmd/CommonNoticeDanmaku.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let cmd = Cmd::deser(json_val).expect("cmd deser error");
+    assert!(matches!(
+        cmd.into_event(),
+        Some(EventData::NoticeEvent(evt)) if evt.kind == NoticeKind::Common
+    ));
+    // 页面挂件的配置，不是通知
+    let json = include_str!("./mock/cmd/WidgetBanner.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    assert!(matches!(
+        Cmd::deser(json_val),
+        Err(crate::cmd::CmdDeserError::Ignored { tag }) if tag == "WIDGET_BANNER"
+    ));
+}
+
+#[test]
+fn danmaku_recall_test() {
+    use crate::event::EventData;
+    // 旧版协议的弹幕没有id_str
+    let json = include_str!("./mock/cmd/DanmuMsg.json");
+    let json_vals: Vec<serde_json::Value> = serde_json::from_str(json).expect("json parse error");
+    for json_val in json_vals {
+        assert!(matches!(
+            Cmd::deser(json_val).expect("cmd deser error").into_event(),
+            Some(EventData::DanmakuEvent(evt)) if evt.id.is_none()
+        ));
+    }
+    let json = include_str!("./mock/cmd/DanmuMsgV2.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let Some(EventData::DanmakuEvent(danmaku)) =
+        Cmd::deser(json_val).expect("cmd deser error").into_event()
+    else {
+        unreachable!("DanmuMsgV2.json should be a danmaku")
+    };
+    assert!(danmaku.id.is_some());
+    let json = include_str!("./mock/cmd/RecallDanmuMsg.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let cmd = Cmd::deser(json_val).expect("cmd deser error");
+    assert!(matches!(
+        cmd.into_event(),
+        Some(EventData::DanmakuRecallEvent(evt)) if danmaku.id.as_ref() == Some(&evt.id)
+    ));
+    let json = include_str!("./mock/cmd/DanmuAggregation.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let cmd = Cmd::deser(json_val).expect("cmd deser error");
+    assert!(matches!(
+        cmd.into_event(),
+        Some(EventData::DanmakuAggregationEvent(evt)) if evt.num == 31
+    ));
+}
+
+#[test]
+#[cfg(feature = "open_platform")]
+fn open_platform_test() {
+    use crate::{
+        event::EventData,
+        model::{GuardLevel, Money},
+    };
+    let cases = [
+        include_str!("./mock/cmd/LiveOpenPlatformDm.json"),
+        include_str!("./mock/cmd/LiveOpenPlatformSendGift.json"),
+        include_str!("./mock/cmd/LiveOpenPlatformSuperChat.json"),
+        include_str!("./mock/cmd/LiveOpenPlatformGuard.json"),
+        include_str!("./mock/cmd/LiveOpenPlatformLike.json"),
+    ]
+    .map(|json| {
+        let json_val = serde_json::from_str(json).expect("json parse error");
+        Cmd::deser(json_val).expect("cmd deser error").into_event()
+    });
+    assert!(matches!(
+        &cases[0],
+        Some(EventData::DanmakuEvent(evt)) if evt.fans_medal.is_some() && evt.ts == 1697716800
+    ));
+    assert!(matches!(
+        &cases[1],
+        Some(EventData::GiftEvent(evt)) if evt.gift.value() == Money::from_gold(500) && evt.fans_medal.is_none()
+    ));
+    assert!(matches!(
+        &cases[2],
+        Some(EventData::SuperChatEvent(evt)) if evt.price == Money::from_cny(30)
+    ));
+    assert!(matches!(
+        &cases[3],
+        Some(EventData::GuardBuyEvent(evt)) if evt.level == GuardLevel::Captain && evt.price == Money::from_gold(198000)
+    ));
+    assert!(matches!(&cases[4], Some(EventData::LikeEvent(_))));
+    // 总价溢出时取上限
+    let mut json_val: serde_json::Value =
+        serde_json::from_str(include_str!("./mock/cmd/LiveOpenPlatformSendGift.json"))
+            .expect("json parse error");
+    json_val["data"]["price"] = u64::MAX.into();
+    json_val["data"]["gift_num"] = 3.into();
+    assert!(matches!(
+        Cmd::deser(json_val).expect("cmd deser error").into_event(),
+        Some(EventData::GiftEvent(evt)) if evt.gift.value() == Money::from_gold(u64::MAX)
+    ));
+    // 开放平台不再下发uid，用户通过open_id区分
+    for case in &cases {
+        let user = match case {
+            Some(EventData::DanmakuEvent(evt)) => Some(&evt.user),
+            Some(EventData::GiftEvent(evt)) => Some(&evt.user),
+            Some(EventData::SuperChatEvent(evt)) => Some(&evt.user),
+            Some(EventData::GuardBuyEvent(evt)) => Some(&evt.user),
+            Some(EventData::LikeEvent(evt)) => Some(&evt.user),
+            _ => None,
+        }
+        .expect("open platform event without user");
+        assert_eq!(user.uid, 0);
+        assert_eq!(
+            user.open_id.as_deref(),
+            Some("39b8fedb-60a5-4e29-ac75-b16955f7e632")
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "open_platform")]
+fn open_platform_sign_test() {
+    let headers = crate::open_platform::sign_headers(
+        "key",
+        "secret",
+        br#"{"code":"ABC","app_id":1}"#,
+        1697716800,
+        "42",
+    );
+    assert_eq!(
+        headers["x-bili-content-md5"],
+        "13ccc4bd6d4cd7056eeb04e6fe441c09"
+    );
+    assert_eq!(
+        headers["Authorization"],
+        "96134c5de312e7e2af19ed5d2cb444c826cf484a92395ed7e1ae3ed67733f061"
+    );
+}
+
+#[test]
+fn moderation_test() {
+    use crate::{event::EventData, model::SilenceKind};
+    let json = include_str!("./mock/cmd/RoomBlockMsg.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let cmd = Cmd::deser(json_val).expect("cmd deser error");
+    assert!(matches!(
+        cmd.into_event(),
+        Some(EventData::UserBlockedEvent(evt)) if evt.user.uid == 1638398734 && evt.operator == 1
+    ));
+    let json = include_str!("./mock/cmd/RoomSilentOn.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let cmd = Cmd::deser(json_val).expect("cmd deser error");
+    assert!(matches!(
+        cmd.into_event(),
+        Some(EventData::RoomSilentOnEvent(evt)) if evt.kind == SilenceKind::Level && evt.second == -1
+    ));
+    let json = include_str!("./mock/cmd/RoomSilentOff.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let cmd = Cmd::deser(json_val).expect("cmd deser error");
+    assert!(matches!(
+        cmd.into_event(),
+        Some(EventData::RoomSilentOffEvent(_))
+    ));
+}
+
+#[test]
+fn room_metadata_test() {
+    use crate::{
+        event::{Event, EventMeta},
+        model::{LiveStatus, RoomMetadata},
+    };
+    fn event(json: &str) -> Event {
+        let json_val = serde_json::from_str(json).expect("json parse error");
+        let data = Cmd::deser(json_val)
+            .expect("cmd deser error")
+            .into_event()
+            .expect("should be an event");
+        Event {
+            data,
+            meta: EventMeta::new(),
+        }
+    }
+    let mut metadata = RoomMetadata {
+        room_id: 21452505,
+        ..Default::default()
+    };
+    assert!(metadata.update(&event(include_str!("./mock/cmd/Live.json"))));
+    assert!(metadata.update(&event(include_str!("./mock/cmd/RoomChange.json"))));
+    assert_eq!(metadata.live_status, LiveStatus::Live);
+    assert_eq!(metadata.live_start_time, Some(1697716800));
+    assert_eq!(metadata.title, "我画我画");
+    assert_eq!(metadata.area_id, 192);
+    assert!(metadata.update(&event(include_str!("./mock/cmd/Preparing.json"))));
+    assert_eq!(metadata.live_status, LiveStatus::Offline);
+    assert_eq!(metadata.live_start_time, None);
+}
+
+#[test]
+fn money_test() {
+    use crate::{event::EventData, model::*};
+    let total: Money = [
+        include_str!("./mock/cmd/SendGift.json"),
+        include_str!("./mock/cmd/SuperChatMessage.json"),
+        include_str!("./mock/cmd/GuardBuy.json"),
+    ]
+    .into_iter()
+    .map(|json| {
+        let json_val = serde_json::from_str(json).expect("json parse error");
+        match Cmd::deser(json_val).expect("cmd deser error").into_event() {
+            // 银瓜子礼物没有现金价值
+            Some(EventData::GiftEvent(evt)) => {
+                assert_eq!(evt.gift.total, Coin::Silver(1000));
+                evt.gift.value()
+            }
+            Some(EventData::SuperChatEvent(evt)) => evt.price,
+            Some(EventData::GuardBuyEvent(evt)) => evt.price,
+            _ => Money::ZERO,
+        }
+    })
+    .sum();
+    assert_eq!(total, Money::from_cny(30 + 198));
+    assert_eq!(total.to_string(), "228.00CNY");
+    // 带上单位序列化，兼容旧版本的数字
+    assert_eq!(
+        serde_json::to_string(&total).expect("ser"),
+        r#"{"gold":228000}"#
+    );
+    assert_eq!(
+        serde_json::from_str::<Money>("228000").expect("deser bare gold"),
+        total
+    );
+
+    // 旧版本中超级留言的价格以元为单位，舰长的价格以金瓜子为单位
+    let json = include_str!("./mock/cmd/SuperChatMessage.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let evt = Cmd::deser(json_val)
+        .expect("cmd deser error")
+        .into_event()
+        .expect("super chat event");
+    let mut stored = serde_json::to_value(&evt).expect("ser event");
+    assert_eq!(
+        stored["data"]["price"],
+        serde_json::json!({ "gold": 30000 })
+    );
+    let restored: EventData = serde_json::from_value(stored.clone()).expect("deser event");
+    assert!(matches!(restored, EventData::SuperChatEvent(evt) if evt.price == Money::from_cny(30)));
+    stored["data"]["price"] = 30.into();
+    let restored: EventData = serde_json::from_value(stored).expect("deser old event");
+    assert!(matches!(restored, EventData::SuperChatEvent(evt) if evt.price == Money::from_cny(30)));
+    let json = include_str!("./mock/cmd/GuardBuy.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let evt = Cmd::deser(json_val)
+        .expect("cmd deser error")
+        .into_event()
+        .expect("guard buy event");
+    let mut stored = serde_json::to_value(&evt).expect("ser event");
+    stored["data"]["price"] = 198000.into();
+    let restored: EventData = serde_json::from_value(stored).expect("deser old event");
+    assert!(matches!(restored, EventData::GuardBuyEvent(evt) if evt.price == Money::from_cny(198)));
+
+    // 旧版本的礼物以coin_type、coin_count和不带单位的price记录价格
+    let json = include_str!("./mock/cmd/SendGift.json");
+    let json_val = serde_json::from_str(json).expect("json parse error");
+    let evt = Cmd::deser(json_val)
+        .expect("cmd deser error")
+        .into_event()
+        .expect("gift event");
+    let current = serde_json::to_value(&evt).expect("ser event");
+    let mut stored = current.clone();
+    let gift = &mut stored["data"]["gift"];
+    gift["coin_type"] = "silver".into();
+    gift["coin_count"] = 1000.into();
+    gift["price"] = 100.into();
+    gift.as_object_mut().expect("gift object").remove("total");
+    let restored: EventData = serde_json::from_value(stored.clone()).expect("deser old gift");
+    assert!(matches!(
+        &restored,
+        EventData::GiftEvent(evt) if evt.gift.price == Coin::Silver(100) && evt.gift.total == Coin::Silver(1000)
+    ));
+    assert_eq!(serde_json::to_value(&restored).expect("ser event"), current);
+    let gift = &mut stored["data"]["gift"];
+    gift["coin_type"] = "gold".into();
+    gift["coin_count"] = 3000.into();
+    gift["price"] = 1000.into();
+    let restored: EventData = serde_json::from_value(stored).expect("deser old gold gift");
+    assert!(matches!(
+        restored,
+        EventData::GiftEvent(evt) if evt.gift.price == Coin::Gold(Money::from_cny(1)) && evt.gift.value() == Money::from_cny(3)
+    ));
+
+    // 溢出时取上限
+    let max = Money::from_gold(u64::MAX);
+    assert_eq!(max + Money::from_cny(1), max);
+    assert_eq!(Money::from_cny(1) * u64::MAX, max);
+    assert_eq!(Money::from_cny(u64::MAX), max);
+    assert!(max.checked_add(Money::from_gold(1)).is_none());
+    assert_eq!(
+        Money::from_gold(100).checked_mul(3),
+        Some(Money::from_battery(3))
+    );
+}
+
+#[test]
+fn guard_level_test() {
+    use crate::model::*;
+    let medal: FansMedal = serde_json::from_str(
+        r#"{"anchor_roomid":21452505,"guard_level":2,"medal_level":21,"medal_name":"测试牌"}"#,
+    )
+    .expect("deser fans medal");
+    assert_eq!(medal.guard_level, GuardLevel::Admiral);
+    let json = serde_json::to_value(&medal).expect("ser fans medal");
+    assert_eq!(json["guard_level"], 2);
+    let flags = DanmakuFlags::new(true, GuardLevel::Captain);
+    assert_eq!(flags, DanmakuFlags(7));
+    assert!(flags.is_lottery());
+    assert_eq!(flags.guard(), GuardLevel::Captain);
+    assert_eq!(serde_json::to_string(&flags).expect("ser flags"), "7");
+}
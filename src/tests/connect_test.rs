@@ -21,3 +21,497 @@
 //     }
 
 // }
+
+#[test]
+#[cfg(feature = "connect")]
+fn anonymous_connector_test() {
+    use crate::{Connector, ConnectorBuildError, DEFAULT_HOST};
+    let connector = Connector::builder(21452505)
+        .anonymous()
+        .build()
+        .expect("anonymous connector");
+    assert_eq!(connector.uid, 0);
+    assert_eq!(connector.host_list.len(), 1);
+    assert_eq!(connector.host_list[0].host, DEFAULT_HOST);
+    assert!(connector.auth().key.is_none());
+
+    let connector = Connector::builder(21452505)
+        .resolve_room(false)
+        .uid(434334701)
+        .token("injected-token")
+        .host_list(vec![crate::default_host()])
+        .build()
+        .expect("injected connector");
+    assert_eq!(connector.auth().key.as_deref(), Some("injected-token"));
+    assert_eq!(connector.auth().uid, 434334701);
+
+    // 缺少的部分需要请求http接口，不会当作匿名模式
+    assert_eq!(
+        Connector::builder(21452505)
+            .token("injected-token")
+            .host_list(vec![crate::default_host()])
+            .build()
+            .err(),
+        Some(ConnectorBuildError::RoomNotResolved)
+    );
+    assert_eq!(
+        Connector::builder(21452505)
+            .resolve_room(false)
+            .host_list(vec![crate::default_host()])
+            .build()
+            .err(),
+        Some(ConnectorBuildError::MissingToken)
+    );
+    assert_eq!(
+        Connector::builder(21452505)
+            .resolve_room(false)
+            .token("injected-token")
+            .build()
+            .err(),
+        Some(ConnectorBuildError::MissingHostList)
+    );
+}
+
+#[test]
+#[cfg(feature = "connect")]
+fn connector_builder_test() {
+    use crate::{Auth, ConnectConfig, Connector, HostStrategy};
+    use std::time::Duration;
+    let auth = Auth::new(434334701, 21452505, None);
+    let config = ConnectConfig::default();
+    assert_eq!(auth.protover, config.protover);
+    assert_eq!(auth.platform, config.platform);
+    assert_eq!(auth.r#type, config.auth_type);
+
+    let mut host = crate::default_host();
+    host.host = String::from("backup.chat.bilibili.com");
+    let connector = Connector::builder(21452505)
+        .anonymous()
+        .host_list(vec![crate::default_host(), host])
+        .protover(2)
+        .platform("android")
+        .auth_type(3)
+        .heartbeat_interval(Duration::from_secs(20))
+        .connect_timeout(Duration::from_secs(5))
+        .buffer_size(16)
+        .host_strategy(HostStrategy::Fixed)
+        .build()
+        .expect("anonymous connector");
+    assert_eq!(connector.config.heartbeat_interval, Duration::from_secs(20));
+    assert_eq!(
+        connector.config.connect_timeout,
+        Some(Duration::from_secs(5))
+    );
+    assert_eq!(connector.config.buffer_size, 16);
+    let auth = connector.auth();
+    assert_eq!(auth.protover, 2);
+    assert_eq!(auth.platform, "android");
+    assert_eq!(auth.r#type, 3);
+    // Fixed只使用host_index指定的host，Sequential从host_index开始依次尝试
+    let mut connector = connector;
+    assert_eq!(connector.use_host(1), Ok("backup.chat.bilibili.com"));
+    let hosts: Vec<_> = connector.hosts().iter().map(|h| h.host.clone()).collect();
+    assert_eq!(hosts, vec!["backup.chat.bilibili.com"]);
+    connector.config.host_strategy = HostStrategy::Sequential;
+    let hosts: Vec<_> = connector.hosts().iter().map(|h| h.host.clone()).collect();
+    assert_eq!(hosts, vec!["backup.chat.bilibili.com", crate::DEFAULT_HOST]);
+
+    // 缓冲区超出上限时丢弃最早的事件
+    let mut buffer = std::collections::VecDeque::new();
+    for i in 0..4 {
+        crate::connection::push_bounded(&mut buffer, i, 3);
+    }
+    assert_eq!(buffer, [1, 2, 3]);
+}
+
+#[test]
+#[cfg(feature = "connect")]
+fn danmu_info_api_test() {
+    use crate::{api::DanmuInfoApi, model::RoomMetadata, Connector};
+    use bilibili_client::api::live::{danmu_info::RoomInfo, room_play_info::DanmuInfoData};
+    use futures_util::future::BoxFuture;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    #[derive(Default)]
+    struct StubApi {
+        danmu_info_calls: AtomicUsize,
+        metadata_calls: AtomicUsize,
+    }
+    impl DanmuInfoApi for StubApi {
+        type Error = ();
+        fn get_room_play_info(&self, _roomid: u64) -> BoxFuture<'_, Result<RoomInfo, ()>> {
+            Box::pin(async {
+                Ok(RoomInfo {
+                    room_id: 21452505,
+                    uid: 434334701,
+                })
+            })
+        }
+        fn get_danmu_info(&self, roomid: u64) -> BoxFuture<'_, Result<DanmuInfoData, ()>> {
+            assert_eq!(roomid, 21452505);
+            let calls = self.danmu_info_calls.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                Ok(DanmuInfoData {
+                    token: format!("stub-token-{calls}"),
+                    host_list: vec![crate::default_host()],
+                })
+            })
+        }
+        fn get_room_metadata(
+            &self,
+            roomid: u64,
+        ) -> BoxFuture<'_, Result<Option<RoomMetadata>, ()>> {
+            self.metadata_calls.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                Ok(Some(RoomMetadata {
+                    room_id: roomid,
+                    ..Default::default()
+                }))
+            })
+        }
+    }
+    let api = Arc::new(StubApi::default());
+    let mut connector = futures::executor::block_on(Connector::builder(1).init_with(api.clone()))
+        .expect("stub api should not fail");
+    assert_eq!(connector.roomid, 21452505);
+    assert_eq!(connector.uid, 434334701);
+    assert_eq!(connector.token.as_deref(), Some("stub-token-0"));
+    // 刷新也通过初始化时的接口请求
+    futures::executor::block_on(connector.refresh()).expect("refresh through stub api");
+    assert_eq!(connector.token.as_deref(), Some("stub-token-1"));
+    assert_eq!(api.danmu_info_calls.load(Ordering::SeqCst), 2);
+    // 通过桩初始化时不创建http客户端
+    assert!(connector.client.get().is_none());
+    // 默认不请求直播间信息
+    assert_eq!(api.metadata_calls.load(Ordering::SeqCst), 0);
+    assert!(connector.metadata().is_none());
+    let connector = futures::executor::block_on(
+        Connector::builder(1)
+            .fetch_metadata(true)
+            .init_with(api.clone()),
+    )
+    .expect("stub api should not fail");
+    assert_eq!(api.metadata_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(connector.metadata().map(|m| m.room_id), Some(21452505));
+
+    // 指定的uid只用于鉴权，房间号仍然会被解析
+    let connector =
+        futures::executor::block_on(Connector::builder(1).uid(0).init_with(StubApi::default()))
+            .expect("stub api should not fail");
+    assert_eq!(connector.roomid, 21452505);
+    assert_eq!(connector.uid, 0);
+}
+
+#[test]
+#[cfg(feature = "connect")]
+fn connector_state_test() {
+    use crate::{Connector, ConnectorState};
+    let connector = Connector::builder(21452505)
+        .resolve_room(false)
+        .uid(434334701)
+        .token("saved-token")
+        .host_list(vec![crate::default_host()])
+        .build()
+        .expect("injected connector");
+    let json = serde_json::to_string(&connector.state()).expect("serialize state");
+    let state: ConnectorState = serde_json::from_str(&json).expect("deserialize state");
+    let restored = Connector::builder(0)
+        .state(state)
+        .build()
+        .expect("restored connector");
+    assert_eq!(restored.roomid, 21452505);
+    assert_eq!(restored.uid, 434334701);
+    assert_eq!(restored.token.as_deref(), Some("saved-token"));
+    assert_eq!(restored.host_list.len(), 1);
+}
+
+#[test]
+#[cfg(feature = "rt_tokio")]
+fn lifecycle_test() {
+    use crate::{connection::ConnectionState, Connection, Connector, Operation, RawPacket};
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime");
+    let mut connector = Connector::builder(21452505)
+        .anonymous()
+        .build()
+        .expect("anonymous connector");
+    let mut lifecycle = connector.lifecycle();
+    rt.block_on(async {
+        // 本地的弹幕服务器：回复鉴权和一次心跳，然后关闭连接
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.expect("accept");
+            let mut ws = tokio_tungstenite::accept_async(stream)
+                .await
+                .expect("ws handshake");
+            let auth = ws.next().await.expect("auth packet").expect("auth packet");
+            let auth = RawPacket::from_buffer(&auth.into_data()).opcode();
+            assert_eq!(auth, Operation::Auth as u32);
+            for (op, body) in [
+                (Operation::AuthReply, &br#"{"code":0}"#[..]),
+                (Operation::HeartbeatReply, &[0, 0, 0, 1][..]),
+            ] {
+                let packet = RawPacket::build(op, body).ser();
+                ws.send(Message::Binary(packet)).await.expect("send");
+            }
+            ws.send(Message::Close(None)).await.expect("close");
+        });
+        let url = reqwest::Url::parse(&format!("ws://{}/sub", addr)).expect("url");
+        let auth = connector.auth().ser();
+        let connection = Connection::connect(url, connector.roomid, auth, &connector)
+            .await
+            .map_err(|e| e.to_string())
+            .expect("connect to local server");
+        let items: Vec<_> = connection.collect().await;
+        assert!(items.iter().any(Result::is_ok));
+        server.await.expect("server");
+    });
+    drop(connector);
+    let mut states = vec![];
+    while let Ok(event) = lifecycle.try_recv() {
+        assert_eq!(event.host, "127.0.0.1");
+        states.push(event.state);
+    }
+    assert_eq!(
+        states,
+        vec![
+            ConnectionState::Authenticated,
+            ConnectionState::HeartbeatOk,
+            ConnectionState::ClosedByServer,
+            ConnectionState::Closed,
+        ]
+    );
+}
+
+#[test]
+#[cfg(feature = "rt_tokio")]
+fn change_room_frame_test() {
+    use crate::{Connection, Connector, Operation, RawPacket};
+    use futures_util::{SinkExt, StreamExt};
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message;
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime");
+    let connector = Connector::builder(21452505)
+        .anonymous()
+        .build()
+        .expect("anonymous connector");
+    let target = Connector::builder(851181)
+        .anonymous()
+        .build()
+        .expect("anonymous connector");
+    rt.block_on(async {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.expect("accept");
+            let mut ws = tokio_tungstenite::accept_async(stream)
+                .await
+                .expect("ws handshake");
+            let mut frames = vec![];
+            while let Some(Ok(Message::Binary(bin))) = ws.next().await {
+                let opcode = RawPacket::from_buffer(&bin).opcode();
+                // 心跳包由单独的任务发送，跳过
+                if opcode == Operation::Heartbeat as u32 {
+                    continue;
+                }
+                let body: serde_json::Value =
+                    serde_json::from_slice(&bin[16..]).expect("json body");
+                frames.push((opcode, body["roomid"].as_u64()));
+                let reply = match opcode {
+                    7 => Operation::AuthReply,
+                    _ => Operation::ChangeRoomReply,
+                };
+                let packet = RawPacket::build(reply, br#"{"code":0}"#).ser();
+                ws.send(Message::Binary(packet)).await.expect("send");
+                if reply == Operation::ChangeRoomReply {
+                    break;
+                }
+            }
+            frames
+        });
+        let url = reqwest::Url::parse(&format!("ws://{}/sub", addr)).expect("url");
+        let auth = connector.auth().ser();
+        let mut connection = Connection::connect(url, connector.roomid, auth, &connector)
+            .await
+            .map_err(|e| e.to_string())
+            .expect("connect to local server");
+        let changed = connection
+            .change_room(&target, Duration::from_secs(5))
+            .await
+            .map_err(|e| format!("{e:?}"))
+            .expect("change room");
+        assert!(changed);
+        let frames = server.await.expect("server");
+        assert_eq!(frames, vec![(7, Some(21452505)), (12, Some(851181))]);
+
+        // 服务器不回复切换，重新连接也失败时关闭原连接
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.expect("accept");
+            let mut ws = tokio_tungstenite::accept_async(stream)
+                .await
+                .expect("ws handshake");
+            while let Some(Ok(message)) = ws.next().await {
+                match message {
+                    Message::Binary(bin) if RawPacket::from_buffer(&bin).opcode() == 7 => {
+                        let packet = RawPacket::build(Operation::AuthReply, br#"{"code":0}"#);
+                        ws.send(Message::Binary(packet.ser())).await.expect("send");
+                    }
+                    Message::Close(_) => return true,
+                    _ => {}
+                }
+            }
+            false
+        });
+        let unreachable = Connector::builder(851181)
+            .resolve_room(false)
+            .token("")
+            .host_list(vec![])
+            .build()
+            .expect("connector without hosts");
+        let url = reqwest::Url::parse(&format!("ws://{}/sub", addr)).expect("url");
+        let auth = connector.auth().ser();
+        let mut connection = Connection::connect(url, connector.roomid, auth, &connector)
+            .await
+            .map_err(|e| e.to_string())
+            .expect("connect to local server");
+        assert!(connection
+            .change_room(&unreachable, Duration::from_millis(100))
+            .await
+            .is_err());
+        assert!(connection.next().await.is_none());
+        assert!(connection.sender().is_closed());
+        assert!(server.await.expect("server"), "close frame sent");
+    });
+}
+
+#[test]
+#[cfg(feature = "connect")]
+fn synchub_lifecycle_test() {
+    use crate::{
+        connection::{synchub::SyncHub, ConnectionState},
+        event::{Event, EventMeta, OnlineRankCountEvent},
+        Connector,
+    };
+    use futures_util::StreamExt;
+    let mut connector = Connector::builder(21452505)
+        .anonymous()
+        .build()
+        .expect("anonymous connector");
+    let mut lifecycle = connector.lifecycle();
+    let event = |count| Event {
+        data: OnlineRankCountEvent { count }.into(),
+        meta: EventMeta::new(),
+    };
+    let mut hub = SyncHub::default();
+    hub.lifecycle = Some(connector.emitter("a,b"));
+    hub.add_channel(futures_util::stream::iter(vec![event(1), event(2)]));
+    hub.add_channel(futures_util::stream::iter(vec![event(3)]));
+    // 所有通道结束后事件流结束
+    let events = futures::executor::block_on(hub.collect::<Vec<_>>());
+    assert_eq!(events.len(), 3);
+    drop(connector);
+    let mut states = vec![];
+    while let Ok(event) = lifecycle.try_recv() {
+        assert_eq!(event.host, "a,b");
+        states.push(event.state);
+    }
+    assert_eq!(states.len(), 2);
+    assert!(matches!(states[0], ConnectionState::Disconnected { .. }));
+    assert_eq!(states[1], ConnectionState::Closed);
+}
+
+#[test]
+#[cfg(feature = "connect")]
+fn raw_packet_test() {
+    use crate::{Operation, RawPacket};
+    let body = br#"{"room_id":21452505}"#;
+    let bin = RawPacket::build(Operation::ChangeRoom, body).ser();
+    let packet = RawPacket::from_buffer(&bin);
+    assert_eq!(packet.opcode(), Operation::ChangeRoom as u32);
+    assert_eq!(bin.len(), 16 + body.len());
+}
+
+#[test]
+#[cfg(feature = "connect")]
+fn receive_notice_test() {
+    use crate::{connection::parse_packet, event::EventData, Operation, RawPacket};
+    let body = include_bytes!("./mock/cmd/NoticeMsg.json");
+    let mut bin = RawPacket::build(Operation::SendMsgReply, body).ser();
+    // 协议版本0，包体是未压缩的json
+    bin[6..8].copy_from_slice(&0_u16.to_be_bytes());
+    assert!(parse_packet(RawPacket::from_buffer(&bin), false).is_empty());
+    let events = parse_packet(RawPacket::from_buffer(&bin), true);
+    assert!(matches!(events.as_slice(), [EventData::NoticeEvent(_)]));
+}
+
+#[test]
+#[cfg(feature = "connect")]
+fn operation_test() {
+    use crate::Operation::*;
+    let ops = [
+        (Handshake, 0),
+        (HandshakeReply, 1),
+        (Heartbeat, 2),
+        (HeartbeatReply, 3),
+        (SendMsg, 4),
+        (SendMsgReply, 5),
+        (DisconnectReply, 6),
+        (Auth, 7),
+        (AuthReply, 8),
+        (Raw, 9),
+        (ProtoReady, 10),
+        (ProtoFinish, 11),
+        (ChangeRoom, 12),
+        (ChangeRoomReply, 13),
+        (Register, 14),
+        (RegisterReply, 15),
+        (Unregister, 16),
+        (UnregisterReply, 17),
+    ];
+    for (op, code) in ops {
+        assert_eq!(op as u32, code, "{:?}", op);
+    }
+}
+
+#[test]
+#[cfg(feature = "connect")]
+fn auth_reply_test() {
+    use crate::{Operation, RawPacket};
+    let ok = RawPacket::build(Operation::AuthReply, br#"{"code":0}"#).ser();
+    assert!(RawPacket::from_buffer(&ok).is_auth_success());
+    let failed = RawPacket::build(Operation::AuthReply, br#"{"code":-101}"#).ser();
+    assert!(!RawPacket::from_buffer(&failed).is_auth_success());
+    // 无法解析的回复当作鉴权失败
+    let garbage = RawPacket::build(Operation::AuthReply, b"<html>").ser();
+    assert!(!RawPacket::from_buffer(&garbage).is_auth_success());
+}
+
+#[test]
+#[cfg(feature = "connect")]
+fn change_room_reply_test() {
+    use crate::{Operation, RawPacket};
+    let ok = RawPacket::build(Operation::ChangeRoomReply, br#"{"code":0}"#).ser();
+    assert!(RawPacket::from_buffer(&ok).is_change_room_success());
+    let refused = RawPacket::build(Operation::ChangeRoomReply, br#"{"code":-101}"#).ser();
+    assert!(!RawPacket::from_buffer(&refused).is_change_room_success());
+    let auth_reply = RawPacket::build(Operation::AuthReply, br#"{"code":0}"#).ser();
+    assert!(!RawPacket::from_buffer(&auth_reply).is_change_room_success());
+}
@@ -0,0 +1,243 @@
+#[test]
+fn enrich_test() {
+    use crate::{
+        enrich::*,
+        event::{Event, EventData, EventMeta, OnlineRankCountEvent},
+        model::*,
+    };
+    use futures_util::{future::BoxFuture, StreamExt};
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    #[derive(Clone, Default)]
+    struct StubResolver(Arc<Mutex<Vec<Vec<u64>>>>);
+    impl ProfileResolver for StubResolver {
+        type Error = ();
+        fn resolve(&self, uids: Vec<u64>) -> BoxFuture<'_, Result<HashMap<u64, Profile>, ()>> {
+            self.0.lock().expect("lock").push(uids.clone());
+            Box::pin(async move {
+                Ok(uids
+                    .into_iter()
+                    .map(|uid| {
+                        let profile = Profile {
+                            uid,
+                            uname: format!("user{uid}"),
+                            face: format!("face{uid}"),
+                            level: Some(uid % 60),
+                        };
+                        (uid, profile)
+                    })
+                    .collect())
+            })
+        }
+
+        fn resolves_level(&self) -> bool {
+            true
+        }
+    }
+    let guard_enter = |uid: u64| Event {
+        data: crate::event::GuardEnterRoomEvent {
+            user: User {
+                uid,
+                uname: String::new(),
+                face: None,
+                level: None,
+                open_id: None,
+            },
+        }
+        .into(),
+        meta: EventMeta::new(),
+    };
+    let events = vec![
+        guard_enter(1),
+        guard_enter(2),
+        Event {
+            data: OnlineRankCountEvent { count: 3 }.into(),
+            meta: EventMeta::new(),
+        },
+        guard_enter(1),
+    ];
+    let resolver = StubResolver::default();
+    let config = EnrichConfig {
+        batch_delay: Duration::from_millis(10),
+        ..Default::default()
+    };
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .expect("runtime");
+    let out: Vec<Event> = rt.block_on(
+        futures_util::stream::iter(events)
+            .enrich_with(resolver.clone(), config)
+            .collect(),
+    );
+    assert_eq!(out.len(), 4);
+    // 不需要补全的事件不用等待
+    assert!(matches!(out[0].data, EventData::OnlineRankCountEvent(_)));
+    for evt in &out[1..] {
+        assert!(matches!(
+            &evt.data,
+            EventData::GuardEnterRoomEvent(evt)
+                if evt.user.face.as_deref() == Some(&*format!("face{}", evt.user.uid))
+                    && evt.user.level == Some(evt.user.uid)
+        ));
+    }
+    // 重复的uid合并到同一次查询
+    assert_eq!(*resolver.0.lock().expect("lock"), vec![vec![1, 2]]);
+
+    let mut cache = ProfileCache::new(2, Duration::from_secs(60));
+    for uid in 1..=3 {
+        cache.insert(Profile {
+            uid,
+            uname: String::new(),
+            face: String::new(),
+            level: None,
+        });
+    }
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get(1).is_none());
+    assert!(cache.get(3).is_some());
+    cache.insert_missing(4);
+    assert!(cache.get(4).is_none());
+    assert!(matches!(cache.lookup(4), Some(None)));
+    assert!(cache.lookup(1).is_none());
+}
+
+#[test]
+fn enrich_bounded_test() {
+    use crate::{
+        enrich::*,
+        event::{Event, EventData, EventMeta},
+        model::*,
+    };
+    use futures_util::{future::BoxFuture, StreamExt};
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+    /// 只知道偶数uid，查询大于100的奇数uid时永远不返回
+    #[derive(Clone, Default)]
+    struct EvenResolver(Arc<Mutex<Vec<Vec<u64>>>>);
+    impl ProfileResolver for EvenResolver {
+        type Error = ();
+        fn resolve(&self, uids: Vec<u64>) -> BoxFuture<'_, Result<HashMap<u64, Profile>, ()>> {
+            self.0.lock().expect("lock").push(uids.clone());
+            Box::pin(async move {
+                if uids.iter().any(|uid| uid % 2 == 1 && *uid > 100) {
+                    std::future::pending::<()>().await;
+                }
+                Ok(uids
+                    .into_iter()
+                    .filter(|uid| uid % 2 == 0)
+                    .map(|uid| {
+                        let profile = Profile {
+                            uid,
+                            uname: format!("user{uid}"),
+                            face: format!("face{uid}"),
+                            level: None,
+                        };
+                        (uid, profile)
+                    })
+                    .collect())
+            })
+        }
+    }
+    let guard_enter = |uid: u64, face: Option<&str>| Event {
+        data: crate::event::GuardEnterRoomEvent {
+            user: User {
+                uid,
+                uname: String::new(),
+                face: face.map(String::from),
+                level: None,
+                open_id: None,
+            },
+        }
+        .into(),
+        meta: EventMeta::new(),
+    };
+    let face = |evt: &Event| match &evt.data {
+        EventData::GuardEnterRoomEvent(evt) => evt.user.face.clone(),
+        _ => None,
+    };
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .expect("runtime");
+
+    // 查询不到的uid在有效期内不再查询，不能查询等级时有头像的用户不查询
+    let resolver = EvenResolver::default();
+    let config = EnrichConfig {
+        batch_delay: Duration::from_millis(10),
+        min_interval: Duration::ZERO,
+        ..Default::default()
+    };
+    let out: Vec<Event> = rt.block_on(async {
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        let mut stream = rx.enrich_with(resolver.clone(), config);
+        tx.unbounded_send(guard_enter(1, None)).expect("send");
+        tx.unbounded_send(guard_enter(2, None)).expect("send");
+        let mut out = vec![
+            stream.next().await.expect("event"),
+            stream.next().await.expect("event"),
+        ];
+        tx.unbounded_send(guard_enter(1, None)).expect("send");
+        tx.unbounded_send(guard_enter(3, Some("face3")))
+            .expect("send");
+        drop(tx);
+        out.extend(stream.collect::<Vec<_>>().await);
+        out
+    });
+    assert_eq!(out.len(), 4);
+    assert_eq!(face(&out[0]), None);
+    assert_eq!(face(&out[1]).as_deref(), Some("face2"));
+    assert_eq!(face(&out[2]), None);
+    assert_eq!(face(&out[3]).as_deref(), Some("face3"));
+    assert_eq!(*resolver.0.lock().expect("lock"), vec![vec![1, 2]]);
+
+    // 查询超时或者等待太久的事件不补全直接推送
+    for (resolve_timeout, max_wait) in [
+        (Duration::from_millis(50), Duration::from_secs(60)),
+        (Duration::from_secs(60), Duration::from_millis(50)),
+    ] {
+        let config = EnrichConfig {
+            batch_delay: Duration::ZERO,
+            resolve_timeout,
+            max_wait,
+            ..Default::default()
+        };
+        let out: Vec<Event> = rt.block_on(
+            futures_util::stream::iter(vec![guard_enter(101, None)])
+                .enrich_with(EvenResolver::default(), config)
+                .collect(),
+        );
+        assert_eq!(out.len(), 1);
+        assert_eq!(face(&out[0]), None);
+    }
+
+    // 暂存的事件达到上限时不再读取上游
+    let pulled = Arc::new(AtomicUsize::new(0));
+    let config = EnrichConfig {
+        batch_delay: Duration::ZERO,
+        max_buffered: 2,
+        ..Default::default()
+    };
+    let events = (0..10)
+        .map(|i| guard_enter(101 + 2 * i, None))
+        .collect::<Vec<_>>();
+    let counter = pulled.clone();
+    let mut stream = futures_util::stream::iter(events)
+        .inspect(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })
+        .enrich_with(EvenResolver::default(), config);
+    let next =
+        rt.block_on(async { tokio::time::timeout(Duration::from_millis(50), stream.next()).await });
+    assert!(next.is_err());
+    assert_eq!(pulled.load(Ordering::SeqCst), 2);
+}
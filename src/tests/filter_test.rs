@@ -0,0 +1,136 @@
+use crate::{
+    cmd::Cmd,
+    event::{Event, EventData, EventMeta},
+    filter::*,
+    model::{GuardLevel, Money},
+};
+
+fn mock_events() -> Vec<Event> {
+    [
+        include_str!("./mock/cmd/DanmuMsg.json"),
+        include_str!("./mock/cmd/SendGift.json"),
+        include_str!("./mock/cmd/SuperChatMessage.json"),
+        include_str!("./mock/cmd/GuardBuy.json"),
+        include_str!("./mock/cmd/InteractWord.json"),
+    ]
+    .into_iter()
+    .flat_map(
+        |json| match serde_json::from_str(json).expect("json parse error") {
+            serde_json::Value::Array(cmds) => cmds,
+            cmd => vec![cmd],
+        },
+    )
+    .filter_map(|json_val| Cmd::deser(json_val).expect("cmd deser error").into_event())
+    .map(|data| Event {
+        data,
+        meta: EventMeta::new(),
+    })
+    .collect()
+}
+
+fn matched(filter: &EventFilter) -> Vec<&'static str> {
+    mock_events()
+        .iter()
+        .filter(|evt| filter.matches(evt))
+        .map(|evt| evt.data.name())
+        .collect()
+}
+
+#[test]
+fn filter_parse_test() {
+    let filter: EventFilter = "kind in [danmaku, gift] && medal.level >= 10"
+        .parse()
+        .expect("parse filter");
+    assert_eq!(matched(&filter), ["DanmakuEvent"; 3]);
+    assert_eq!(
+        filter,
+        EventFilter::kind(["danmaku", "gift"]) & Field::MedalLevel.ge(10)
+    );
+
+    let filter: EventFilter = "value >= 10cny || !(kind != super_chat) || guard >= captain"
+        .parse()
+        .expect("parse filter");
+    assert_eq!(matched(&filter), ["SuperChatEvent", "GuardBuyEvent"]);
+
+    let filter: EventFilter = r#"kind == danmaku && message contains "晚安""#
+        .parse()
+        .expect("parse filter");
+    assert_eq!(matched(&filter), ["DanmakuEvent"]);
+
+    let filter: EventFilter = "gift.value < 1.5cny && user.uid == 8794913"
+        .parse()
+        .expect("parse filter");
+    assert_eq!(matched(&filter), ["GiftEvent"]);
+    assert_eq!(
+        filter,
+        Field::GiftValue.lt(Money::from_gold(1500)) & Field::Uid.eq(8794913)
+    );
+
+    // 缺少字段时条件为假
+    let filter: EventFilter = "user.level != 0".parse().expect("parse filter");
+    assert!(matched(&filter).is_empty());
+    assert_eq!(matched(&EventFilter::All).len(), 8);
+}
+
+#[test]
+fn filter_error_test() {
+    let err = |src: &str| {
+        src.parse::<EventFilter>()
+            .expect_err("should not parse")
+            .position
+    };
+    assert_eq!(err("medal.lv >= 10"), 0);
+    assert_eq!(err("medal.level >= 10cny"), 15);
+    assert_eq!(err("value > 10yuan"), 8);
+    assert_eq!(err("kind in [danmaku, gfit]"), 18);
+    assert_eq!(err("kind > danmaku"), 0);
+    assert_eq!(err("(guard == captain"), 17);
+    assert_eq!(err("guard == admiral gift"), 17);
+}
+
+#[test]
+fn filter_display_serde_test() {
+    let src = r#"kind in [danmaku, GiftEvent] && (guard >= admiral || value >= 1500gold) && !(user.name contains "\"")"#;
+    let filter: EventFilter = src.parse().expect("parse filter");
+    assert_eq!(filter.to_string(), src);
+    assert_eq!(
+        filter.to_string().parse::<EventFilter>(),
+        Ok(filter.clone())
+    );
+
+    #[derive(serde::Deserialize)]
+    struct Config {
+        filter: EventFilter,
+    }
+    let config: Config =
+        serde_json::from_str(&serde_json::json!({ "filter": src }).to_string()).expect("deser");
+    assert_eq!(config.filter, filter);
+    assert_eq!(
+        serde_json::to_value(&filter).expect("ser"),
+        serde_json::Value::String(src.to_owned())
+    );
+    assert_eq!(
+        Field::Guard.ge(GuardLevel::Governor).to_string(),
+        "guard >= governor"
+    );
+}
+
+#[test]
+fn filter_stream_test() {
+    use futures::StreamExt;
+    let filter: EventFilter = "kind == guard_buy || kind == enter_room"
+        .parse()
+        .expect("parse");
+    assert!(matches!(filter, EventFilter::Or(..)));
+    let filter = Field::Kind.eq("guard_buy");
+    let events = futures::executor::block_on(
+        futures::stream::iter(mock_events())
+            .filter_events(filter)
+            .collect::<Vec<_>>(),
+    );
+    assert_eq!(events.len(), 1);
+    assert!(matches!(
+        &events[0].data,
+        EventData::GuardBuyEvent(evt) if evt.level == GuardLevel::Captain
+    ));
+}
@@ -4,3 +4,31 @@ mod cmd_test;
 
 #[cfg(test)]
 mod connect_test;
+
+#[cfg(test)]
+#[cfg(feature = "connect")]
+mod filter_test;
+
+#[cfg(test)]
+#[cfg(feature = "rt_tokio")]
+mod room_manager_test;
+
+#[cfg(test)]
+#[cfg(feature = "connect")]
+mod send_test;
+
+#[cfg(test)]
+#[cfg(feature = "connect")]
+mod moderation_test;
+
+#[cfg(test)]
+#[cfg(feature = "rt_tokio")]
+mod enrich_test;
+
+#[cfg(test)]
+#[cfg(feature = "rt_tokio")]
+mod router_test;
+
+#[cfg(test)]
+#[cfg(feature = "rt_tokio")]
+mod record_test;
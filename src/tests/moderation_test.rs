@@ -0,0 +1,77 @@
+#[test]
+fn moderation_api_test() {
+    use crate::{api::ApiResponse, model::SilenceKind, moderation::*, Connector};
+    let mut connector = Connector::builder(21452505)
+        .anonymous()
+        .build()
+        .expect("anonymous connector");
+    assert!(matches!(
+        connector.moderation_form(vec![("tuid", String::from("33778290"))]),
+        Err(ModerationError::MissingCsrf)
+    ));
+    let login_info =
+        serde_json::from_value(serde_json::json!({ "bili_jct": "8a3d2b1c" })).expect("login info");
+    connector.set_login_info(login_info);
+    let silence = RoomSilence::On {
+        kind: SilenceKind::Medal,
+        level: 5,
+        minutes: 30,
+    };
+    let form = connector
+        .moderation_form(silence.form())
+        .expect("moderation form");
+    let form = |key: &str| {
+        form.iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.as_str())
+    };
+    assert_eq!(form("type"), Some("medal"));
+    assert_eq!(form("level"), Some("5"));
+    assert_eq!(form("minute"), Some("30"));
+    assert_eq!(form("room_id"), Some("21452505"));
+    assert_eq!(form("csrf"), Some("8a3d2b1c"));
+    assert_eq!(form("csrf_token"), Some("8a3d2b1c"));
+    assert_eq!(MuteDuration::ThisLive.hour(), 0);
+    assert_eq!(MuteDuration::Forever.hour(), -1);
+
+    let parse = |json: serde_json::Value| {
+        serde_json::from_value::<ApiResponse>(json)
+            .expect("response")
+            .into_data::<BannedUserPage, ModerationError>()
+    };
+    let page = parse(serde_json::json!({
+        "code": 0,
+        "message": "0",
+        "data": {
+            "data": [{
+                "tuid": 33778290,
+                "tname": "ASD设计",
+                "name": "主播",
+                "ctime": "2023-10-19 20:00:00",
+                "block_end_time": "2023-10-19 21:00:00"
+            }],
+            "total": 1,
+            "total_page": 1
+        }
+    }))
+    .expect("banned users");
+    assert_eq!((page.total, page.users.len()), (1, 1));
+    assert_eq!(page.users[0].uid, 33778290);
+    assert_eq!(page.users[0].operator_name, "主播");
+    assert!(matches!(
+        parse(serde_json::json!({ "code": -101, "message": "账号未登录" })),
+        Err(ModerationError::NotLoggedIn)
+    ));
+    assert!(matches!(
+        parse(serde_json::json!({ "code": -400, "message": "请求错误" })),
+        Err(ModerationError::BadRequest { message }) if message == "请求错误"
+    ));
+    assert!(matches!(
+        parse(serde_json::json!({ "code": 1, "message": "非房管" })),
+        Err(ModerationError::Api { code: 1, .. })
+    ));
+    assert!(matches!(
+        parse(serde_json::json!({ "code": 0, "data": { "total": "many" } })),
+        Err(ModerationError::Deser(_))
+    ));
+}
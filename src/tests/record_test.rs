@@ -0,0 +1,118 @@
+#[test]
+fn record_replay_test() {
+    use crate::{
+        event::{Event, EventMeta, LiveStartEvent, LiveStopEvent, OnlineRankCountEvent},
+        record::*,
+    };
+    use futures_util::StreamExt;
+    use std::time::{Duration, Instant};
+    let start = chrono::Utc::now();
+    let at = |secs: i64, data: crate::event::EventData| Event {
+        data,
+        meta: EventMeta {
+            time: start + chrono::Duration::seconds(secs),
+            ..EventMeta::new()
+        },
+    };
+    let events = vec![
+        at(0, OnlineRankCountEvent { count: 1 }.into()),
+        at(
+            1,
+            LiveStartEvent {
+                room_id: 1,
+                live_time: 0,
+            }
+            .into(),
+        ),
+        at(2, OnlineRankCountEvent { count: 2 }.into()),
+        at(3, LiveStopEvent { room_id: 1 }.into()),
+    ];
+    let dir = std::env::temp_dir().join(format!(
+        "bilive-record-{}",
+        start.timestamp_nanos_opt().unwrap_or_default()
+    ));
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .expect("runtime");
+
+    let recorder = Recorder::new(&dir).compress(true).rotate_per_session(true);
+    let recorded: Vec<Event> = rt.block_on(
+        futures_util::stream::iter(events)
+            .record(recorder)
+            .collect(),
+    );
+    assert_eq!(recorded.len(), 4);
+    let mut files: Vec<_> = std::fs::read_dir(&dir)
+        .expect("read dir")
+        .map(|entry| entry.expect("entry").path())
+        .collect();
+    files.sort();
+    // 开播前一个文件，开播到下播一个文件
+    assert_eq!(files.len(), 2);
+    assert!(files
+        .iter()
+        .all(|path| path.to_string_lossy().ends_with(".jsonl.br")));
+
+    let replay = |path: &std::path::Path, speed| {
+        let replayer = Replayer::open(path, speed).expect("open record");
+        rt.block_on(replayer.collect::<Vec<Event>>())
+    };
+    let session = replay(&files[1], ReplaySpeed::Instant);
+    assert_eq!(session.len(), 3);
+    assert!(matches!(
+        session[0].data,
+        crate::event::EventData::LiveStartEvent(_)
+    ));
+    assert_eq!(session[2].meta.time, start + chrono::Duration::seconds(3));
+
+    let begin = Instant::now();
+    let session = replay(&files[1], ReplaySpeed::Scaled(100.0));
+    assert_eq!(session.len(), 3);
+    assert!(begin.elapsed() >= Duration::from_millis(20));
+
+    // 无法解析的行被跳过
+    let replayer = Replayer::new(
+        std::io::Cursor::new(format!(
+            "not json\n\n{}\n",
+            serde_json::to_string(&recorded[0]).expect("ser")
+        )),
+        ReplaySpeed::Original,
+    );
+    assert_eq!(rt.block_on(replayer.collect::<Vec<Event>>()).len(), 1);
+
+    // 刷新后没有关闭的压缩文件也可以读出已经写入的事件
+    let mut recorder = Recorder::new(&dir).compress(true).flush_every(2);
+    for event in &recorded[..3] {
+        recorder.record(event).expect("record");
+    }
+    let path = recorder.path().expect("recording path").to_owned();
+    assert_eq!(replay(&path, ReplaySpeed::Instant).len(), 2);
+    recorder.close().expect("close record");
+    assert_eq!(replay(&path, ReplaySpeed::Instant).len(), 3);
+
+    // 没有新事件时写入线程也会按时刷新
+    let idle_dir = dir.join("idle");
+    let recorder = Recorder::new(&idle_dir)
+        .compress(true)
+        .flush_interval(Duration::from_millis(20));
+    let (sender, receiver) = futures::channel::mpsc::unbounded();
+    let mut recording = receiver.record(recorder);
+    rt.block_on(async {
+        sender
+            .unbounded_send(recorded[0].clone())
+            .expect("send event");
+        recording.next().await.expect("recorded event");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    });
+    let path = std::fs::read_dir(&idle_dir)
+        .expect("read dir")
+        .next()
+        .expect("record file")
+        .expect("entry")
+        .path();
+    assert_eq!(replay(&path, ReplaySpeed::Instant).len(), 1);
+    drop(sender);
+    assert!(rt.block_on(recording.next()).is_none());
+    std::fs::remove_dir_all(&dir).expect("remove record dir");
+}
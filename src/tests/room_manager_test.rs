@@ -0,0 +1,82 @@
+#[test]
+fn room_manager_test() {
+    use crate::{api::DanmuInfoApi, RoomManager, RoomManagerError};
+    use bilibili_client::{
+        api::live::{danmu_info::RoomInfo, room_play_info::DanmuInfoData},
+        reqwest_client::{Client, ClientError, LoginInfo},
+    };
+    use futures_util::future::BoxFuture;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    // 短号1对应长房间号21452505，没有弹幕服务器，连接任务不会访问网络
+    #[derive(Default)]
+    struct StubApi {
+        room_play_info_calls: AtomicUsize,
+        danmu_info_calls: AtomicUsize,
+    }
+    impl DanmuInfoApi for StubApi {
+        type Error = ClientError;
+        fn get_room_play_info(&self, roomid: u64) -> BoxFuture<'_, Result<RoomInfo, ClientError>> {
+            self.room_play_info_calls.fetch_add(1, Ordering::SeqCst);
+            let room_id = if roomid == 1 { 21452505 } else { roomid };
+            Box::pin(async move {
+                Ok(RoomInfo {
+                    room_id,
+                    uid: 434334701,
+                })
+            })
+        }
+        fn get_danmu_info(
+            &self,
+            _roomid: u64,
+        ) -> BoxFuture<'_, Result<DanmuInfoData, ClientError>> {
+            self.danmu_info_calls.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async {
+                Ok(DanmuInfoData {
+                    token: String::new(),
+                    host_list: vec![],
+                })
+            })
+        }
+    }
+    let api = Arc::new(StubApi::default());
+    let (manager, _events) =
+        RoomManager::with_api(api.clone(), Client::default(), LoginInfo::default(), 1);
+    // 不在tokio运行时中
+    assert!(matches!(
+        futures::executor::block_on(manager.add_room(1)),
+        Err(RoomManagerError::NoRuntime)
+    ));
+    assert!(manager.rooms().is_empty());
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .expect("runtime");
+    rt.block_on(async {
+        assert_eq!(manager.add_room(1).await.expect("add short id"), 21452505);
+        assert!(matches!(
+            manager.add_room(21452505).await,
+            Err(RoomManagerError::AlreadyAdded(21452505))
+        ));
+        assert_eq!(manager.add_room(851181).await.expect("add long id"), 851181);
+        // 没有host，连接失败后1秒重连
+        tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    });
+    // 房间号只在添加时解析，重连只请求弹幕服务器信息
+    assert_eq!(api.room_play_info_calls.load(Ordering::SeqCst), 3);
+    assert!(api.danmu_info_calls.load(Ordering::SeqCst) >= 4);
+    let health = manager.health(851181).expect("health of added room");
+    assert!(health.reconnect_count >= 2);
+    let mut rooms = manager.rooms();
+    rooms.sort();
+    assert_eq!(rooms, vec![851181, 21452505]);
+    let health = manager.health(21452505).expect("health of added room");
+    assert_eq!(health.room_id, Some(21452505));
+    assert!(manager.health(1).is_none());
+    assert!(manager.remove_room(21452505));
+    assert!(!manager.remove_room(21452505));
+    assert_eq!(manager.rooms(), vec![851181]);
+}
@@ -0,0 +1,155 @@
+#[test]
+fn router_test() {
+    use crate::{
+        event::{DanmakuEvent, Event, EventMeta, GuardEnterRoomEvent},
+        model::*,
+        router::*,
+        Connector,
+    };
+    use futures_util::StreamExt;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+    let user = |uid: u64| User {
+        uid,
+        uname: format!("user{uid}"),
+        face: None,
+        level: None,
+        open_id: None,
+    };
+    let danmaku = |uid: u64, message: &str| Event {
+        data: DanmakuEvent {
+            id: None,
+            flag: DanmakuFlags::default(),
+            message: DanmakuMessage::Plain {
+                message: message.to_owned(),
+            },
+            user: user(uid),
+            fans_medal: None,
+            ts: 0,
+        }
+        .into(),
+        meta: EventMeta::new(),
+    };
+    let events = vec![
+        danmaku(1, "!Ping a  b"),
+        danmaku(1, "！ping"),
+        danmaku(2, "hello"),
+        danmaku(3, "！ping c"),
+        Event {
+            data: GuardEnterRoomEvent { user: user(4) }.into(),
+            meta: EventMeta::new(),
+        },
+    ];
+
+    let commands = Arc::new(Mutex::new(vec![]));
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+    let pulled = Arc::new(AtomicUsize::new(0));
+    let handled = Arc::new(Mutex::new(vec![]));
+    let guard_enters = Arc::new(AtomicUsize::new(0));
+    let filter: crate::filter::EventFilter = "kind != guard_enter_room".parse().expect("filter");
+    let router = Router::new()
+        .middleware(filter)
+        .middleware(RateLimit::per_user(Duration::from_secs(60)).commands_only())
+        .command("ping", {
+            let (commands, handled) = (commands.clone(), handled.clone());
+            move |cmd: Command, ctx: Context| {
+                let (commands, handled) = (commands.clone(), handled.clone());
+                async move {
+                    let uid = ctx.event.data.user().map(|user| user.uid);
+                    // 记录这时限制了并发的处理器已经开始处理的事件数量
+                    let started = handled.lock().expect("lock").len();
+                    commands
+                        .lock()
+                        .expect("lock")
+                        .push((uid, cmd.args, started));
+                    Err("handler errors are only logged".into())
+                }
+            }
+        })
+        .route(
+            Handler::on({
+                let (running, max_running) = (running.clone(), max_running.clone());
+                let (pulled, handled) = (pulled.clone(), handled.clone());
+                move |evt: DanmakuEvent, _ctx| {
+                    let (running, max_running) = (running.clone(), max_running.clone());
+                    let (pulled, handled) = (pulled.clone(), handled.clone());
+                    async move {
+                        handled
+                            .lock()
+                            .expect("lock")
+                            .push((evt.message.to_string(), pulled.load(Ordering::SeqCst)));
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_running.fetch_max(now, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(5)).await;
+                        running.fetch_sub(1, Ordering::SeqCst);
+                        Ok(())
+                    }
+                }
+            })
+            .concurrency(1),
+        )
+        .on({
+            let guard_enters = guard_enters.clone();
+            move |_evt: GuardEnterRoomEvent, _ctx| {
+                guard_enters.fetch_add(1, Ordering::SeqCst);
+                async { Ok(()) }
+            }
+        });
+
+    let connector = Connector::builder(21452505)
+        .anonymous()
+        .build()
+        .expect("anonymous connector");
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .expect("runtime");
+    let stream = futures_util::stream::iter(events).inspect({
+        let pulled = pulled.clone();
+        move |_| {
+            pulled.fetch_add(1, Ordering::SeqCst);
+        }
+    });
+    rt.block_on(router.run(stream, connector));
+
+    // 用户1的第二条命令被限流
+    let commands = commands.lock().expect("lock");
+    assert_eq!(
+        commands
+            .iter()
+            .map(|(uid, args, _)| (*uid, args.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (Some(1), vec![String::from("a"), String::from("b")]),
+            (Some(3), vec![String::from("c")]),
+        ]
+    );
+    // 命令不需要等待限制了并发的处理器处理完前面的事件
+    assert!(commands.iter().all(|(_, _, started)| *started <= 1));
+    assert_eq!(max_running.load(Ordering::SeqCst), 1);
+    // 达到并发上限的事件在处理器自己的队列中等待，处理顺序和事件顺序相同，
+    // 不影响从事件流中取事件，第一个事件开始处理时已经取完了所有事件
+    assert_eq!(
+        *handled.lock().expect("lock"),
+        vec![
+            (String::from("!Ping a  b"), 5),
+            (String::from("hello"), 5),
+            (String::from("！ping c"), 5),
+        ]
+    );
+    assert_eq!(guard_enters.load(Ordering::SeqCst), 0);
+    assert_eq!(
+        Command::parse("!", "!roll 1 6"),
+        Some(Command {
+            name: String::from("roll"),
+            args: vec![String::from("1"), String::from("6")],
+        })
+    );
+    assert_eq!(Command::parse("!", "! "), None);
+}
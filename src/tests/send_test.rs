@@ -0,0 +1,65 @@
+#[test]
+fn send_danmaku_check_test() {
+    use crate::{send::*, Connector};
+    use bilibili_client::reqwest_client::LoginInfo;
+    assert!(matches!(
+        check_message("  ", DANMAKU_DEFAULT_MAX_LENGTH),
+        Err(SendDanmakuError::Empty)
+    ));
+    assert!(check_message("二十个字以内的弹幕可以发送", DANMAKU_DEFAULT_MAX_LENGTH).is_ok());
+    assert!(matches!(
+        check_message(&"弹".repeat(21), DANMAKU_DEFAULT_MAX_LENGTH),
+        Err(SendDanmakuError::TooLong { len: 21, max: 20 })
+    ));
+    assert!(matches!(
+        SendDanmakuError::from_code(10030, String::new()),
+        Some(SendDanmakuError::TooFast)
+    ));
+    assert!(matches!(
+        SendDanmakuError::from_code(1003, String::new()),
+        Some(SendDanmakuError::Muted)
+    ));
+    assert!(matches!(
+        SendDanmakuError::from_code(-400, String::from("请求错误")),
+        Some(SendDanmakuError::BadRequest { .. })
+    ));
+    assert!(SendDanmakuError::from_code(0, String::new()).is_none());
+
+    let connector = |roomid: u64| {
+        Connector::builder(roomid)
+            .anonymous()
+            .build()
+            .expect("anonymous connector")
+    };
+    // 未登录时没有csrf，不会请求接口
+    assert!(connector(21452505).csrf().is_none());
+    assert!(matches!(
+        futures::executor::block_on(
+            connector(21452505).send_danmaku("你好", &DanmakuOptions::default())
+        ),
+        Err(SendDanmakuError::MissingCsrf)
+    ));
+    let login_info: LoginInfo =
+        serde_json::from_value(serde_json::json!({ "bili_jct": "8a3d2b1c" })).expect("login info");
+    let mut logged_in = connector(21452505);
+    logged_in.set_login_info(login_info.clone());
+    assert_eq!(logged_in.csrf(), Some("8a3d2b1c"));
+
+    // 同一个连接器的克隆共享发送间隔，其他连接器（其他账号）互不影响
+    let reservation = logged_in.reserve_danmaku_slot().expect("first slot");
+    assert!(matches!(
+        logged_in.clone().reserve_danmaku_slot(),
+        Err(SendDanmakuError::RateLimited { .. })
+    ));
+    let mut other_account = connector(21452505);
+    other_account.set_login_info(login_info);
+    assert!(other_account.reserve_danmaku_slot().is_ok());
+    // 请求失败时归还发送机会
+    logged_in.release_danmaku_slot(reservation);
+    let reservation = logged_in.reserve_danmaku_slot().expect("released slot");
+    // 之后已经有新的发送时，旧的占用不会覆盖它
+    logged_in.release_danmaku_slot(reservation);
+    let _newer = logged_in.reserve_danmaku_slot().expect("newer slot");
+    logged_in.release_danmaku_slot(reservation);
+    assert!(logged_in.reserve_danmaku_slot().is_err());
+}